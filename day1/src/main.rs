@@ -9,6 +9,67 @@ macro_rules! dprintln {
     }
 }
 
+#[cfg(feature = "part1")]
+#[derive(Debug, Clone, Copy)]
+enum DistanceMetric {
+    Absolute,
+    Squared,
+    Maximum,
+    Relative,
+}
+
+#[cfg(feature = "part1")]
+impl DistanceMetric {
+    fn parse(input: &str) -> Self {
+        match input {
+            "abs" | "absolute" => DistanceMetric::Absolute,
+            "squared" => DistanceMetric::Squared,
+            "max" | "maximum" => DistanceMetric::Maximum,
+            "relative" => DistanceMetric::Relative,
+            _ => panic!("Invalid metric: {}", input),
+        }
+    }
+
+    fn distance(&self, a: i32, b: i32) -> f64 {
+        let delta = (a as f64 - b as f64).abs();
+        match self {
+            DistanceMetric::Absolute | DistanceMetric::Maximum => delta,
+            DistanceMetric::Squared => delta * delta,
+            DistanceMetric::Relative => {
+                // |a - b| / max(|a|, |b|), two zeros are considered equal
+                let scale = (a as f64).abs().max((b as f64).abs());
+                if scale == 0.0 {
+                    0.0
+                } else {
+                    delta / scale
+                }
+            }
+        }
+    }
+
+    fn aggregate(&self, distances: impl Iterator<Item = f64>) -> f64 {
+        match self {
+            DistanceMetric::Maximum => distances.fold(0.0, f64::max),
+            _ => distances.sum(),
+        }
+    }
+}
+
+#[cfg(feature = "part1")]
+#[derive(Debug)]
+struct Pairing {
+    a: i32,
+    b: i32,
+    distance: f64,
+}
+
+#[cfg(feature = "part1")]
+impl std::fmt::Display for Pairing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> {}: {}", self.a, self.b, self.distance)
+    }
+}
+
 fn main() {
     let mut list_a = vec![];
     let mut list_b = vec![];
@@ -22,7 +83,7 @@ fn main() {
             let line = line.unwrap();
             line.split(' ')
                 .filter(|s| !s.is_empty())
-                .map(|s| i32::from_str_radix(s, 10).unwrap())
+                .map(|s| s.parse::<i32>().unwrap())
                 .take(2)
                 .for_each(|x| {
                     if list_a.len() <= list_b.len() {
@@ -39,6 +100,16 @@ fn main() {
 
     #[cfg(feature = "part1")]
     {
+        // --metric <abs|squared|max|relative> selects how pairs are compared
+        // --pairs prints every matched pair with its distance
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        let metric = args
+            .iter()
+            .position(|a| a == "--metric")
+            .map(|i| DistanceMetric::parse(args.get(i + 1).expect("Missing metric")))
+            .unwrap_or(DistanceMetric::Absolute);
+        let print_pairs = args.iter().any(|a| a == "--pairs");
+
         // Sort the lists
         list_a.sort();
         list_b.sort();
@@ -46,19 +117,29 @@ fn main() {
         dprintln!("Sorted List A: {:?}", list_a);
         dprintln!("Sorted List B: {:?}", list_b);
 
-        let distances = list_a
+        let pairings = list_a
             .iter()
             .zip(list_b.iter())
-            .map(|(a, b)| (a - b).abs())
+            .map(|(&a, &b)| Pairing {
+                a,
+                b,
+                distance: metric.distance(a, b),
+            })
             .collect::<Vec<_>>();
 
-        dprintln!("Distances: {:?}", distances);
+        dprintln!("Pairings: {:?}", pairings);
 
-        let sum: i32 = distances.iter().sum();
+        if print_pairs {
+            for pairing in pairings.iter() {
+                println!("{}", pairing);
+            }
+        }
 
-        dprintln!("Sum: {}", sum);
+        let total = metric.aggregate(pairings.iter().map(|p| p.distance));
 
-        println!("{}", sum);
+        dprintln!("Total ({:?}): {}", metric, total);
+
+        println!("{}", total);
     }
 
     #[cfg(feature = "part2")]
@@ -1,4 +1,10 @@
-use std::io::BufRead;
+use std::{collections::BTreeMap, io::BufRead};
+use rayon::prelude::*;
+
+mod policy;
+mod repair;
+use policy::{SafetyPolicy, ViolationKind};
+use repair::{Repair, RepairObjective};
 
 macro_rules! dprintln {
     ($($arg:tt)*) => {
@@ -14,68 +20,352 @@ struct Report {
     pub levels: Vec<i32>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dampened {
+    // Safe without removing anything
+    Safe,
+    // Safe once the level at this index is removed
+    SafeWithRemoved(usize),
+    Unsafe,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Violation {
+    kind: ViolationKind,
+    indices: (usize, usize),
+    values: (i32, i32),
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} between levels {} and {} ({} -> {})",
+            self.kind, self.indices.0, self.indices.1, self.values.0, self.values.1
+        )
+    }
+}
+
+#[derive(Debug)]
+struct Verdict {
+    // Direction the report was judged against, 0 when the policy allows any
+    direction: i32,
+    violations: Vec<Violation>,
+}
+
+impl Verdict {
+    fn is_safe(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
 impl Report {
-    
-    fn level_deltas_s(levels: &[i32]) -> Vec<i32> {
-        levels.iter()
-            .zip(levels.iter().skip(1))
-            .map(|(a, b)| b - a)
-            .collect()
+    fn parse(line: &str) -> Self {
+        let levels = line
+            .split(char::is_whitespace)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<i32>().unwrap())
+            .collect::<Vec<_>>();
+        Report { levels }
     }
 
-    fn is_safe_s(levels: &[i32]) -> (bool, (bool, bool, bool)) {
-        let deltas = Self::level_deltas_s(levels);
-        let is_increasing = deltas.iter().all(|&d| d >= 1);
-        let is_decreasing = deltas.iter().all(|&d| d <= -1);
-        let is_delta_change_safe = deltas.iter().map(|d| d.abs()).all(|d| d >= 1 && d <= 3);
-    
-        let is_safe = (is_increasing || is_decreasing) && is_delta_change_safe;
-        (is_safe, (is_increasing, is_decreasing, is_delta_change_safe))
+    fn explanation(&self, policy: &SafetyPolicy) -> String {
+        let verdict = self.verdict(policy);
+        let direction = match verdict.direction {
+            1 => "increasing",
+            -1 => "decreasing",
+            _ => "any direction",
+        };
+        let mut result = match verdict.is_safe() {
+            true => format!("{:?}: safe ({})", self.levels, direction),
+            false => format!("{:?}: unsafe ({})", self.levels, direction),
+        };
+        for violation in verdict.violations.iter() {
+            result.push_str(&format!("\n  {}", violation));
+        }
+        result
     }
 
-    fn is_safe(&self) -> (bool, (bool, bool, bool)) {
-        Self::is_safe_s(self.levels.as_slice())
+    fn repair_summary(&self, policy: &SafetyPolicy, objective: RepairObjective) -> String {
+        match self.repair(policy, objective) {
+            Some(repair) => format!(
+                "{:?} -> {:?} ({} edits, {} total change)",
+                self.levels, repair.levels, repair.edits, repair.total_change
+            ),
+            None => format!("{:?}: no {:?} repair under this policy", self.levels, objective),
+        }
+    }
+
+    fn is_safe_s(levels: &[i32], policy: &SafetyPolicy) -> bool {
+        policy
+            .directions()
+            .iter()
+            .any(|&direction| Self::first_unsafe_pair_s(levels, policy, direction, None).is_none())
+    }
+
+    fn is_safe(&self, policy: &SafetyPolicy) -> bool {
+        Self::is_safe_s(self.levels.as_slice(), policy)
+    }
+
+    fn verdict_s(levels: &[i32], policy: &SafetyPolicy, direction: i32) -> Verdict {
+        let violations = levels
+            .windows(2)
+            .enumerate()
+            .filter_map(|(i, pair)| {
+                policy
+                    .pair_violation(pair[0], pair[1], direction)
+                    .map(|kind| Violation {
+                        kind,
+                        indices: (i, i + 1),
+                        values: (pair[0], pair[1]),
+                    })
+            })
+            .collect();
+        Verdict { direction, violations }
+    }
+
+    // Violations against the direction the report breaks the least
+    fn verdict(&self, policy: &SafetyPolicy) -> Verdict {
+        policy
+            .directions()
+            .iter()
+            .map(|&direction| Self::verdict_s(self.levels.as_slice(), policy, direction))
+            .min_by_key(|verdict| verdict.violations.len())
+            .unwrap()
     }
 
-    fn is_safe_with_single_ignored(&self) -> bool {
-        let (is_safe, _) = Self::is_safe_s(self.levels.as_slice());
-        if is_safe {
-            return true;
+    // Index of the left level of the first unsafe pair, pretending the level at `skip` is not there
+    fn first_unsafe_pair_s(
+        levels: &[i32],
+        policy: &SafetyPolicy,
+        direction: i32,
+        skip: Option<usize>,
+    ) -> Option<usize> {
+        let mut previous: Option<usize> = None;
+        for i in (0..levels.len()).filter(|&i| Some(i) != skip) {
+            if let Some(p) = previous {
+                if !policy.is_safe_pair(levels[p], levels[i], direction) {
+                    return Some(p);
+                }
+            }
+            previous = Some(i);
         }
-        for i in 0..self.levels.len() {
-            let mut levels = self.levels.clone();
-            levels.remove(i);
-            let (is_safe, _) = Self::is_safe_s(levels.as_slice());
-            if is_safe {
-                return true;
+        None
+    }
+
+    fn dampen_s(levels: &[i32], policy: &SafetyPolicy, direction: i32) -> Dampened {
+        // Removing a level can only fix the first unsafe pair by removing one of its two levels,
+        // so at most two more linear passes are needed
+        let i = match Self::first_unsafe_pair_s(levels, policy, direction, None) {
+            None => return Dampened::Safe,
+            Some(i) => i,
+        };
+        [i, i + 1]
+            .into_iter()
+            .find(|&r| Self::first_unsafe_pair_s(levels, policy, direction, Some(r)).is_none())
+            .map_or(Dampened::Unsafe, Dampened::SafeWithRemoved)
+    }
+
+    fn dampen(&self, policy: &SafetyPolicy) -> Dampened {
+        let verdicts = policy
+            .directions()
+            .iter()
+            .map(|&direction| Self::dampen_s(self.levels.as_slice(), policy, direction))
+            .collect::<Vec<_>>();
+        if verdicts.contains(&Dampened::Safe) {
+            return Dampened::Safe;
+        }
+        verdicts
+            .into_iter()
+            .find(|&d| d != Dampened::Unsafe)
+            .unwrap_or(Dampened::Unsafe)
+    }
+
+    // Indices of the longest subsequence where every consecutive pair is safe
+    fn longest_safe_subsequence_s(levels: &[i32], policy: &SafetyPolicy, direction: i32) -> Vec<usize> {
+        // Longest subsequence ending at each value seen so far, as (length, index)
+        let mut best_by_value: BTreeMap<i32, (usize, usize)> = BTreeMap::new();
+        let mut previous = vec![None; levels.len()];
+        let mut best_end: Option<(usize, usize)> = None;
+
+        for (j, &level) in levels.iter().enumerate() {
+            let before = policy
+                .predecessor_ranges(level, direction)
+                .into_iter()
+                .flat_map(|range| best_by_value.range(range).map(|(_, &entry)| entry))
+                .max_by_key(|&(length, _)| length);
+            previous[j] = before.map(|(_, i)| i);
+            let length = before.map_or(1, |(length, _)| length + 1);
+
+            let entry = best_by_value.entry(level).or_insert((length, j));
+            if entry.0 < length {
+                *entry = (length, j);
+            }
+            if best_end.is_none_or(|(best, _)| best < length) {
+                best_end = Some((length, j));
             }
         }
-        return false;
+
+        let mut kept = vec![];
+        let mut current = best_end.map(|(_, j)| j);
+        while let Some(j) = current {
+            kept.push(j);
+            current = previous[j];
+        }
+        kept.reverse();
+        kept
+    }
+
+    fn repair(&self, policy: &SafetyPolicy, objective: RepairObjective) -> Option<Repair> {
+        repair::repair(self.levels.as_slice(), policy, objective)
+    }
+
+    // Smallest set of level indices whose removal makes the report safe
+    fn minimum_removals(&self, policy: &SafetyPolicy) -> Vec<usize> {
+        let kept = policy
+            .directions()
+            .iter()
+            .map(|&direction| Self::longest_safe_subsequence_s(self.levels.as_slice(), policy, direction))
+            .max_by_key(|kept| kept.len())
+            .unwrap();
+        let mut kept = kept.into_iter().peekable();
+        (0..self.levels.len())
+            .filter(|&i| {
+                if kept.peek() == Some(&i) {
+                    kept.next();
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect()
     }
     
 }
 
+// Reports evaluated in parallel at once while streaming
+const STREAM_BATCH_SIZE: usize = 4096;
+
+struct Classification {
+    is_safe: bool,
+    is_dampened_safe: bool,
+    // Explanation and repair lines requested for this report
+    output: Vec<String>,
+}
+
+fn classify(
+    report: &Report,
+    policy: &SafetyPolicy,
+    explain: bool,
+    repair_objective: Option<RepairObjective>,
+) -> Classification {
+    let is_safe = report.is_safe(policy);
+    let mut output = vec![];
+    if explain {
+        output.push(report.explanation(policy));
+    }
+    if let Some(objective) = repair_objective.filter(|_| !is_safe) {
+        output.push(report.repair_summary(policy, objective));
+    }
+    Classification {
+        is_safe,
+        is_dampened_safe: report.dampen(policy) != Dampened::Unsafe,
+        output,
+    }
+}
+
 fn main() {
+    // --rank prints reports ordered by how many levels must be removed to make them safe
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let rank = args.iter().any(|a| a == "--rank");
+    // --explain prints why each report is or is not safe
+    let explain = args.iter().any(|a| a == "--explain");
+    // --repair <edits|change> suggests corrected levels for unsafe reports
+    let repair_objective = args
+        .iter()
+        .position(|a| a == "--repair")
+        .map(|i| RepairObjective::parse(args.get(i + 1).expect("Missing repair objective")));
+    // --stream classifies reports in parallel batches as they are read instead of loading them all
+    let stream = args.iter().any(|a| a == "--stream");
+    // --policy <file>, --min-delta N, --max-delta N, --allow-equal and --any-direction set the safety rule
+    let policy = SafetyPolicy::from_args(&args);
+    dprintln!("Policy: {:?}", policy);
+
+    if stream {
+        if rank {
+            panic!("--rank needs every report and cannot be combined with --stream");
+        }
+        let mut safe_reports = 0;
+        let mut safe_reports_with_single_ignored = 0;
+        let stdin = std::io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            let batch = lines
+                .by_ref()
+                .take(STREAM_BATCH_SIZE)
+                .map(|line| Report::parse(&line.unwrap()))
+                .collect::<Vec<_>>();
+            if batch.is_empty() {
+                break;
+            }
+            // Indexed parallel iterators keep the input order when collected
+            let classified = batch
+                .par_iter()
+                .map(|r| classify(r, &policy, explain, repair_objective))
+                .collect::<Vec<_>>();
+            for classification in classified {
+                safe_reports += classification.is_safe as usize;
+                safe_reports_with_single_ignored += classification.is_dampened_safe as usize;
+                for line in classification.output {
+                    println!("{}", line);
+                }
+            }
+        }
+        println!("Safe reports: {}", safe_reports);
+        println!("Safe reports with single ignored: {}", safe_reports_with_single_ignored);
+        return;
+    }
+
     let mut reports = vec![];
     let stdin = std::io::stdin();
     {
         let lock = stdin.lock();
         // Read input line by line
         for line in lock.lines() {
-            let levels = line.unwrap()
-                .split(char::is_whitespace)
-                .filter(|s| !s.is_empty())
-                .map(|s| i32::from_str_radix(s, 10).unwrap())
-                .collect::<Vec<_>>();
-            reports.push(Report { levels });
+            reports.push(Report::parse(&line.unwrap()));
         }
     }
     dprintln!("Reports: {:?}", reports);
 
-    let safe_reports = reports.iter().filter(|r| r.is_safe().0).count();
-    let safe_reports_with_single_ignored = reports.iter().filter(|r| r.is_safe_with_single_ignored()).count();
+    let safe_reports = reports.iter().filter(|r| r.is_safe(&policy)).count();
+    let dampened = reports.iter().map(|r| r.dampen(&policy)).collect::<Vec<_>>();
+    dprintln!("Dampened: {:?}", dampened);
+    let safe_reports_with_single_ignored = dampened.iter().filter(|&&d| d != Dampened::Unsafe).count();
 
     
     println!("Safe reports: {}", safe_reports);
     println!("Safe reports with single ignored: {}", safe_reports_with_single_ignored);
+
+    if explain {
+        for report in reports.iter() {
+            println!("{}", report.explanation(&policy));
+        }
+    }
+
+    if let Some(objective) = repair_objective {
+        for report in reports.iter().filter(|r| !r.is_safe(&policy)) {
+            println!("{}", report.repair_summary(&policy, objective));
+        }
+    }
+
+    if rank {
+        let mut ranked = reports
+            .iter()
+            .map(|r| (r, r.minimum_removals(&policy)))
+            .collect::<Vec<_>>();
+        ranked.sort_by_key(|(_, removed)| std::cmp::Reverse(removed.len()));
+        for (report, removed) in ranked {
+            println!("{} removed {:?}: {:?}", removed.len(), removed, report.levels);
+        }
+    }
 }
@@ -0,0 +1,171 @@
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    DirectionFlip,
+    DeltaTooSmall,
+    DeltaTooLarge,
+}
+
+impl std::fmt::Display for ViolationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ViolationKind::DirectionFlip => "direction flip",
+            ViolationKind::DeltaTooSmall => "delta too small",
+            ViolationKind::DeltaTooLarge => "delta too large",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SafetyPolicy {
+    // Bounds on the absolute difference of non-equal neighbours
+    pub min_delta: i32,
+    pub max_delta: i32,
+    // Equal neighbours are accepted regardless of the delta bounds
+    pub allow_equal: bool,
+    // All changes must go in the same direction
+    pub monotone: bool,
+}
+
+impl Default for SafetyPolicy {
+    fn default() -> Self {
+        SafetyPolicy {
+            min_delta: 1,
+            max_delta: 3,
+            allow_equal: false,
+            monotone: true,
+        }
+    }
+}
+
+impl SafetyPolicy {
+    fn set(&mut self, key: &str, value: &str) {
+        let parse_bool = |value: &str| match value {
+            "true" | "yes" | "1" => true,
+            "false" | "no" | "0" => false,
+            _ => panic!("Invalid boolean for {}: {}", key, value),
+        };
+        let parse_int = |value: &str| {
+            value
+                .parse::<i32>()
+                .unwrap_or_else(|_| panic!("Invalid number for {}: {}", key, value))
+        };
+        match key {
+            "min_delta" => self.min_delta = parse_int(value),
+            "max_delta" => self.max_delta = parse_int(value),
+            "allow_equal" => self.allow_equal = parse_bool(value),
+            "monotone" => self.monotone = parse_bool(value),
+            _ => panic!("Unknown policy key: {}", key),
+        }
+    }
+
+    // Config file of `key = value` lines, `#` starts a comment
+    pub fn parse(config: &str) -> Self {
+        let mut policy = SafetyPolicy::default();
+        for line in config.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .unwrap_or_else(|| panic!("Invalid policy line: {}", line));
+            policy.set(key.trim(), value.trim());
+        }
+        policy
+    }
+
+    pub fn load(path: &str) -> Self {
+        let config = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Failed to read policy {}: {}", path, e));
+        Self::parse(&config)
+    }
+
+    // --policy <file> is loaded first, the remaining flags override it
+    pub fn from_args(args: &[String]) -> Self {
+        let mut policy = args
+            .iter()
+            .position(|a| a == "--policy")
+            .map(|i| Self::load(args.get(i + 1).expect("Missing policy path")))
+            .unwrap_or_default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--min-delta" => policy.set("min_delta", args.next().expect("Missing min delta")),
+                "--max-delta" => policy.set("max_delta", args.next().expect("Missing max delta")),
+                "--allow-equal" => policy.allow_equal = true,
+                "--any-direction" => policy.monotone = false,
+                _ => {}
+            }
+        }
+        policy
+    }
+
+    // Directions a report may follow, 0 means any
+    pub fn directions(&self) -> &'static [i32] {
+        if self.monotone {
+            &[1, -1]
+        } else {
+            &[0]
+        }
+    }
+
+    pub fn pair_violation(&self, a: i32, b: i32, direction: i32) -> Option<ViolationKind> {
+        let delta = b - a;
+        if delta == 0 {
+            return (!self.allow_equal).then_some(ViolationKind::DeltaTooSmall);
+        }
+        if direction != 0 && delta.signum() != direction {
+            return Some(ViolationKind::DirectionFlip);
+        }
+        if delta.abs() < self.min_delta {
+            Some(ViolationKind::DeltaTooSmall)
+        } else if delta.abs() > self.max_delta {
+            Some(ViolationKind::DeltaTooLarge)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_safe_pair(&self, a: i32, b: i32, direction: i32) -> bool {
+        self.pair_violation(a, b, direction).is_none()
+    }
+
+    // Allowed steps as one contiguous range, None when there is a gap in it
+    pub fn step_range(&self, direction: i32) -> Option<(i32, i32)> {
+        let min = self.min_delta.max(1);
+        let max = self.max_delta;
+        let (lo, hi) = match (self.allow_equal, min <= max) {
+            (true, false) => return Some((0, 0)),
+            (false, false) => return None,
+            (true, true) if min == 1 => (0, max),
+            (true, true) => return None,
+            (false, true) => (min, max),
+        };
+        match direction {
+            1 => Some((lo, hi)),
+            -1 => Some((-hi, -lo)),
+            // Steps both ways only join up around 0
+            _ => (lo == 0).then_some((-hi, hi)),
+        }
+    }
+
+    // Values a level may directly follow while keeping the pair safe
+    pub fn predecessor_ranges(&self, level: i32, direction: i32) -> Vec<RangeInclusive<i32>> {
+        let min = self.min_delta.max(1);
+        let mut ranges = vec![];
+        if direction >= 0 {
+            ranges.push(level.saturating_sub(self.max_delta)..=level.saturating_sub(min));
+        }
+        if direction <= 0 {
+            ranges.push(level.saturating_add(min)..=level.saturating_add(self.max_delta));
+        }
+        if self.allow_equal {
+            ranges.push(level..=level);
+        }
+        ranges.retain(|range| !range.is_empty());
+        ranges
+    }
+}
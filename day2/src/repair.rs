@@ -0,0 +1,463 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::policy::SafetyPolicy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairObjective {
+    // Fewest changed levels
+    Edits,
+    // Smallest sum of absolute changes
+    TotalChange,
+}
+
+impl RepairObjective {
+    pub fn parse(input: &str) -> Self {
+        match input {
+            "edits" => RepairObjective::Edits,
+            "change" => RepairObjective::TotalChange,
+            _ => panic!("Invalid repair objective: {}", input),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Repair {
+    pub levels: Vec<i32>,
+    pub edits: usize,
+    pub total_change: i64,
+}
+
+impl Repair {
+    fn new(original: &[i32], levels: Vec<i64>) -> Option<Self> {
+        let levels = levels
+            .into_iter()
+            .map(|level| i32::try_from(level).ok())
+            .collect::<Option<Vec<_>>>()?;
+        let edits = original.iter().zip(levels.iter()).filter(|(a, b)| a != b).count();
+        let total_change = original
+            .iter()
+            .zip(levels.iter())
+            .map(|(&a, &b)| (a as i64 - b as i64).abs())
+            .sum();
+        Some(Repair {
+            levels,
+            edits,
+            total_change,
+        })
+    }
+}
+
+pub fn repair(levels: &[i32], policy: &SafetyPolicy, objective: RepairObjective) -> Option<Repair> {
+    let candidates = policy.directions().iter().filter_map(|&direction| {
+        let repaired = match objective {
+            RepairObjective::Edits => repair_edits(levels, policy, direction)?,
+            RepairObjective::TotalChange => repair_total_change(levels, policy, direction)?,
+        };
+        Repair::new(levels, repaired)
+    });
+    match objective {
+        RepairObjective::Edits => candidates.min_by_key(|r| (r.edits, r.total_change)),
+        RepairObjective::TotalChange => candidates.min_by_key(|r| (r.total_change, r.edits)),
+    }
+}
+
+// Non-zero step bounds, None when the policy allows no non-zero step at all
+fn nonzero_bounds(policy: &SafetyPolicy) -> Option<(i64, i64)> {
+    let min = policy.min_delta.max(1) as i64;
+    let max = policy.max_delta as i64;
+    (min <= max).then_some((min, max))
+}
+
+// How `steps` safe steps can add up to `delta`, as (non-zero steps, of which going up)
+fn find_split(policy: &SafetyPolicy, delta: i64, steps: i64, direction: i32) -> Option<(i64, i64)> {
+    let bounds = nonzero_bounds(policy);
+    let feasible = |nonzero: i64, up: i64| -> bool {
+        if nonzero == 0 {
+            return delta == 0;
+        }
+        let Some((min, max)) = bounds else {
+            return false;
+        };
+        let down = nonzero - up;
+        up * min - down * max <= delta && delta <= up * max - down * min
+    };
+    let up_for = |nonzero: i64| -> i64 {
+        match direction {
+            1 => nonzero,
+            -1 => 0,
+            // Fewest steps going up that can still reach the delta
+            _ => match bounds {
+                Some((min, max)) => (delta + nonzero * min).div_euclid(min + max)
+                    + ((delta + nonzero * min).rem_euclid(min + max) != 0) as i64,
+                None => 0,
+            }
+            .clamp(0, nonzero),
+        }
+    };
+
+    if !policy.allow_equal {
+        return feasible(steps, up_for(steps)).then_some((steps, up_for(steps)));
+    }
+    match (direction, bounds) {
+        // Monotone steps with zeros allowed, the non-zero step count is bounded directly by the delta
+        (1 | -1, _) if delta == 0 => Some((0, 0)),
+        (1 | -1, Some((min, max))) => {
+            let distance = delta * direction as i64;
+            let nonzero = (distance + max - 1).div_euclid(max).max(1);
+            (distance > 0 && nonzero * min <= distance && nonzero <= steps)
+                .then_some((nonzero, up_for(nonzero)))
+        }
+        _ => (0..=steps)
+            .map(|nonzero| (nonzero, up_for(nonzero)))
+            .find(|&(nonzero, up)| feasible(nonzero, up)),
+    }
+}
+
+// Split `total` into `parts` values as even as possible
+fn spread(total: i64, parts: i64) -> impl Iterator<Item = i64> {
+    (0..parts).map(move |i| total.div_euclid(parts) + (i < total.rem_euclid(parts)) as i64)
+}
+
+// Steps leading from one kept level to another
+fn steps_between(policy: &SafetyPolicy, delta: i64, steps: i64, direction: i32) -> Vec<i64> {
+    let (nonzero, up) = find_split(policy, delta, steps, direction).unwrap();
+    let down = nonzero - up;
+    let mut result = vec![];
+    if nonzero > 0 {
+        let (min, max) = nonzero_bounds(policy).unwrap();
+        let rise = (delta + down * min).clamp(up * min, up * max);
+        result.extend(spread(rise, up));
+        result.extend(spread(rise - delta, down).map(|s| -s));
+    }
+    result.resize(steps as usize, 0);
+    result
+}
+
+// Single step used to extend a repaired report past its first and last kept level
+fn filler_step(policy: &SafetyPolicy, direction: i32) -> Option<i64> {
+    match nonzero_bounds(policy) {
+        Some((min, _)) if direction < 0 => Some(-min),
+        Some((min, _)) => Some(min),
+        None => policy.allow_equal.then_some(0),
+    }
+}
+
+// Keep the longest chain of levels that can be joined by safe steps, and rebuild the rest
+fn repair_edits(levels: &[i32], policy: &SafetyPolicy, direction: i32) -> Option<Vec<i64>> {
+    if levels.is_empty() {
+        return Some(vec![]);
+    }
+    let filler = filler_step(policy, direction);
+    if filler.is_none() && levels.len() > 1 {
+        return None;
+    }
+    let levels = levels.iter().map(|&l| l as i64).collect::<Vec<_>>();
+
+    let mut chain = vec![1usize; levels.len()];
+    let mut previous = vec![None; levels.len()];
+    for j in 0..levels.len() {
+        for i in 0..j {
+            if chain[i] + 1 > chain[j]
+                && find_split(policy, levels[j] - levels[i], (j - i) as i64, direction).is_some()
+            {
+                chain[j] = chain[i] + 1;
+                previous[j] = Some(i);
+            }
+        }
+    }
+
+    let mut kept = vec![];
+    let mut current = (0..levels.len()).max_by_key(|&j| (chain[j], Reverse(j)));
+    while let Some(j) = current {
+        kept.push(j);
+        current = previous[j];
+    }
+    kept.reverse();
+
+    let mut repaired = levels.clone();
+    let (first, last) = (kept[0], kept[kept.len() - 1]);
+    for i in (0..first).rev() {
+        repaired[i] = repaired[i + 1] - filler.unwrap();
+    }
+    for pair in kept.windows(2) {
+        let (i, j) = (pair[0], pair[1]);
+        let steps = steps_between(policy, levels[j] - levels[i], (j - i) as i64, direction);
+        for (k, step) in steps.into_iter().enumerate() {
+            repaired[i + k + 1] = repaired[i + k] + step;
+        }
+    }
+    for i in last + 1..levels.len() {
+        repaired[i] = repaired[i - 1] + filler.unwrap();
+    }
+    Some(repaired)
+}
+
+// Minimize the total absolute change with slope trick when the allowed steps form one contiguous
+// range, and over candidate values otherwise
+fn repair_total_change(levels: &[i32], policy: &SafetyPolicy, direction: i32) -> Option<Vec<i64>> {
+    let Some((lo, hi)) = policy.step_range(direction) else {
+        return repair_total_change_split(levels, policy, direction);
+    };
+    let (lo, hi) = (lo as i64, hi as i64);
+
+    // Breakpoints of the convex cost of the current level, left and right of its minimum,
+    // stored without the lazy shifts applied so far
+    let mut left: BinaryHeap<i64> = BinaryHeap::new();
+    let mut right: BinaryHeap<Reverse<i64>> = BinaryHeap::new();
+    let (mut shift_left, mut shift_right) = (0i64, 0i64);
+    let mut minimizers = Vec::with_capacity(levels.len());
+
+    for (i, &level) in levels.iter().enumerate() {
+        let level = level as i64;
+        if i > 0 {
+            // Allowing the next level to be any safe step away stretches the minimum
+            shift_left += lo;
+            shift_right += hi;
+        }
+        // Add |x - level| as max(0, x - level) + max(0, level - x)
+        left.push(level - shift_left);
+        let top = left.pop().unwrap() + shift_left;
+        right.push(Reverse(top - shift_right));
+        right.push(Reverse(level - shift_right));
+        let Reverse(bottom) = right.pop().unwrap();
+        left.push(bottom + shift_right - shift_left);
+
+        minimizers.push(left.peek().unwrap() + shift_left);
+    }
+
+    let mut repaired = minimizers.clone();
+    for i in (0..repaired.len().saturating_sub(1)).rev() {
+        repaired[i] = minimizers[i].clamp(repaired[i + 1] - hi, repaired[i + 1] - lo);
+    }
+    Some(repaired)
+}
+
+// Allowed steps as separate ranges, going up, going down and staying equal
+fn step_ranges(policy: &SafetyPolicy, direction: i32) -> Vec<(i64, i64)> {
+    let mut ranges = vec![];
+    if let Some((min, max)) = nonzero_bounds(policy) {
+        if direction >= 0 {
+            ranges.push((min, max));
+        }
+        if direction <= 0 {
+            ranges.push((-max, -min));
+        }
+    }
+    if policy.allow_equal {
+        ranges.push((0, 0));
+    }
+    ranges
+}
+
+// Values some optimal repair is made of. Fixing which range each step is in turns the problem into
+// a linear program over difference constraints, whose optimum sits where each level is an original
+// level moved by a number of steps of the smallest or largest size, fewer than the number of
+// levels. So every level is within `(n - 1) * max` of an original level, and the candidates are
+// those whole ranges, merged, rather than every level with every offset.
+fn candidate_values(levels: &[i32], policy: &SafetyPolicy) -> Vec<i64> {
+    let reach = match nonzero_bounds(policy) {
+        Some((_, max)) => (levels.len() as i64 - 1) * max,
+        None => 0,
+    };
+    let mut sorted = levels.iter().map(|&level| level as i64).collect::<Vec<_>>();
+    sorted.sort();
+    sorted.dedup();
+    let mut candidates: Vec<i64> = vec![];
+    for level in sorted {
+        let start = candidates.last().map_or(level - reach, |&last| (last + 1).max(level - reach));
+        candidates.extend(start..=level + reach);
+    }
+    candidates
+}
+
+// For each candidate, the cheapest previous candidate a step in `lo..=hi` below it, as
+// (cost, index). The window slides right as the candidates grow, so a deque of increasing costs
+// keeps its minimum.
+fn window_minima(candidates: &[i64], costs: &[Option<i64>], lo: i64, hi: i64) -> Vec<Option<(i64, usize)>> {
+    let mut minima = vec![];
+    let mut window = std::collections::VecDeque::new();
+    let mut next = 0;
+    for &value in candidates {
+        while next < candidates.len() && candidates[next] <= value - lo {
+            if let Some(cost) = costs[next] {
+                while window.back().is_some_and(|&(c, _)| c >= cost) {
+                    window.pop_back();
+                }
+                window.push_back((cost, next));
+            }
+            next += 1;
+        }
+        while window.front().is_some_and(|&(_, i)| candidates[i] < value - hi) {
+            window.pop_front();
+        }
+        minima.push(window.front().copied());
+    }
+    minima
+}
+
+// Total change repair when the allowed steps have gaps, dynamic programming over the candidate
+// values of each level
+fn repair_total_change_split(levels: &[i32], policy: &SafetyPolicy, direction: i32) -> Option<Vec<i64>> {
+    if levels.is_empty() {
+        return Some(vec![]);
+    }
+    let candidates = candidate_values(levels, policy);
+    let ranges = step_ranges(policy, direction);
+    let change = |level: i32, value: i64| (level as i64 - value).abs();
+
+    let mut costs = candidates
+        .iter()
+        .map(|&value| Some(change(levels[0], value)))
+        .collect::<Vec<_>>();
+    let mut previous = vec![];
+    for &level in levels[1..].iter() {
+        let minima = ranges
+            .iter()
+            .map(|&(lo, hi)| window_minima(&candidates, &costs, lo, hi))
+            .collect::<Vec<_>>();
+        let best = (0..candidates.len())
+            .map(|c| minima.iter().filter_map(|m| m[c]).min())
+            .collect::<Vec<_>>();
+        costs = best
+            .iter()
+            .zip(candidates.iter())
+            .map(|(best, &value)| best.map(|(cost, _)| cost + change(level, value)))
+            .collect();
+        // Back pointers of unreachable candidates are never followed, and u32 halves the memory
+        // of long reports
+        previous.push(best.into_iter().map(|b| b.map_or(0, |(_, i)| i as u32)).collect::<Vec<_>>());
+    }
+
+    let (_, mut current) = costs
+        .iter()
+        .enumerate()
+        .filter_map(|(c, cost)| Some((cost.as_ref()?, c)))
+        .min()?;
+    let mut repaired = vec![candidates[current]];
+    for previous in previous.iter().rev() {
+        current = previous[current] as usize;
+        repaired.push(candidates[current]);
+    }
+    repaired.reverse();
+    Some(repaired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheapest safe report by each objective, trying every sequence of values near the levels
+    fn brute_force(levels: &[i32], policy: &SafetyPolicy) -> Option<(usize, i64)> {
+        let reach = (levels.len() as i32 - 1) * policy.max_delta.max(1);
+        let low = levels.iter().min().unwrap() - reach;
+        let high = levels.iter().max().unwrap() + reach;
+        let mut best: Option<(usize, i64)> = None;
+        let mut best_change: Option<i64> = None;
+        let mut current = vec![];
+        fn search(
+            levels: &[i32],
+            policy: &SafetyPolicy,
+            range: (i32, i32),
+            current: &mut Vec<i32>,
+            best: &mut Option<(usize, i64)>,
+            best_change: &mut Option<i64>,
+        ) {
+            if current.len() == levels.len() {
+                let safe = policy.directions().iter().any(|&direction| {
+                    current.windows(2).all(|pair| policy.is_safe_pair(pair[0], pair[1], direction))
+                });
+                if safe {
+                    let edits = levels.iter().zip(current.iter()).filter(|(a, b)| a != b).count();
+                    let change = levels.iter().zip(current.iter()).map(|(&a, &b)| (a - b).abs() as i64).sum();
+                    *best = Some(best.map_or((edits, change), |b| b.min((edits, change))));
+                    *best_change = Some(best_change.map_or(change, |b| b.min(change)));
+                }
+                return;
+            }
+            for value in range.0..=range.1 {
+                current.push(value);
+                search(levels, policy, range, current, best, best_change);
+                current.pop();
+            }
+        }
+        search(levels, policy, (low, high), &mut current, &mut best, &mut best_change);
+        Some((best?.0, best_change?))
+    }
+
+    fn policies() -> Vec<SafetyPolicy> {
+        let mut policies = vec![];
+        for (min_delta, max_delta) in [(1, 3), (2, 3), (1, 1), (2, 2), (3, 2)] {
+            for allow_equal in [false, true] {
+                for monotone in [false, true] {
+                    policies.push(SafetyPolicy {
+                        min_delta,
+                        max_delta,
+                        allow_equal,
+                        monotone,
+                    });
+                }
+            }
+        }
+        policies
+    }
+
+    // Small pseudo random reports, the same on every run
+    fn reports() -> Vec<Vec<i32>> {
+        let mut seed = 12345u64;
+        let mut next = move |bound: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) % bound) as i32
+        };
+        let mut reports = vec![vec![5, 5, 9], vec![1, 2, 7, 8], vec![3]];
+        for _ in 0..40 {
+            let length = 1 + next(3) as usize;
+            reports.push((0..length).map(|_| next(8)).collect());
+        }
+        reports
+    }
+
+    fn is_safe(levels: &[i32], policy: &SafetyPolicy) -> bool {
+        policy.directions().iter().any(|&direction| {
+            levels.windows(2).all(|pair| policy.is_safe_pair(pair[0], pair[1], direction))
+        })
+    }
+
+    #[test]
+    fn repairs_match_brute_force() {
+        for policy in policies() {
+            for levels in reports() {
+                let expected = brute_force(&levels, &policy);
+                for objective in [RepairObjective::Edits, RepairObjective::TotalChange] {
+                    let found = repair(&levels, &policy, objective);
+                    let context = format!("{:?} {:?} {:?}", levels, policy, objective);
+                    let (Some(found), Some((edits, change))) = (found.as_ref(), expected) else {
+                        assert_eq!(found.is_some(), expected.is_some(), "{}", context);
+                        continue;
+                    };
+                    assert!(is_safe(&found.levels, &policy), "{} gave {:?}", context, found.levels);
+                    match objective {
+                        RepairObjective::Edits => assert_eq!(found.edits, edits, "{}", context),
+                        RepairObjective::TotalChange => assert_eq!(found.total_change, change, "{}", context),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn candidates_grow_linearly() {
+        let policy = SafetyPolicy {
+            min_delta: 1,
+            max_delta: 3,
+            allow_equal: false,
+            monotone: false,
+        };
+        // Levels spread over 0..100 as in the puzzle input
+        let levels = (0..20_000).map(|i| i * 37 % 100).collect::<Vec<_>>();
+        let candidates = candidate_values(&levels, &policy);
+        assert!(candidates.len() <= 2 * levels.len() * 3 + 100, "{} candidates", candidates.len());
+
+        let found = repair(&levels[..500], &policy, RepairObjective::TotalChange).unwrap();
+        assert!(is_safe(&found.levels, &policy));
+    }
+}
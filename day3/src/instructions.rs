@@ -0,0 +1,174 @@
+use std::ops::RangeInclusive;
+
+// Operands are written as 1 to 3 digit numbers
+const MAX_OPERAND_DIGITS: usize = 3;
+
+// Outcome of matching a pattern at the start of the input
+#[derive(Debug)]
+pub enum Match<T> {
+    // Value and the number of bytes it spans
+    Found(T, usize),
+    // The input ended before the pattern could be decided
+    Incomplete,
+    None,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Semantics {
+    // Produces a value that is added to the total
+    Value(fn(&[i64]) -> i64),
+    // Computes the new enabled state from the current one
+    Toggle(fn(bool) -> bool),
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub name: String,
+    pub arity: RangeInclusive<usize>,
+    pub semantics: Semantics,
+}
+
+#[derive(Debug)]
+pub struct InstructionSet {
+    instructions: Vec<Instruction>,
+    // Opcodes of the instructions whose name starts with a given byte
+    by_first_byte: Vec<Vec<usize>>,
+}
+
+impl InstructionSet {
+    pub fn new() -> Self {
+        InstructionSet {
+            instructions: Vec::new(),
+            by_first_byte: vec![Vec::new(); 256],
+        }
+    }
+
+    pub fn register(&mut self, name: &str, arity: RangeInclusive<usize>, semantics: Semantics) -> &mut Self {
+        assert!(!name.is_empty(), "Instruction name must not be empty");
+        let opcode = self.instructions.len();
+        self.by_first_byte[name.as_bytes()[0] as usize].push(opcode);
+        self.instructions.push(Instruction {
+            name: name.to_string(),
+            arity,
+            semantics,
+        });
+        self
+    }
+
+    // mul(a,b), do() and don't()
+    pub fn standard() -> Self {
+        let mut set = InstructionSet::new();
+        set.register("mul", 2..=2, Semantics::Value(|x| x[0] * x[1]))
+            .register("do", 0..=0, Semantics::Toggle(|_| true))
+            .register("don't", 0..=0, Semantics::Toggle(|_| false));
+        set
+    }
+
+    // Variadic mul, add and sub, and toggle() flipping the enabled state. The product of 6 operands
+    // of up to 999 is the most that still fits an i64, 999^7 does not.
+    pub fn extended() -> Self {
+        let mut set = InstructionSet::new();
+        set.register("mul", 2..=6, Semantics::Value(|x| x.iter().product()))
+            .register("add", 2..=8, Semantics::Value(|x| x.iter().sum()))
+            .register("sub", 2..=8, Semantics::Value(|x| x[0] - x[1..].iter().sum::<i64>()))
+            .register("do", 0..=0, Semantics::Toggle(|_| true))
+            .register("don't", 0..=0, Semantics::Toggle(|_| false))
+            .register("toggle", 0..=0, Semantics::Toggle(|enabled| !enabled));
+        set
+    }
+
+    pub fn get(&self, opcode: usize) -> &Instruction {
+        &self.instructions[opcode]
+    }
+
+    // Instruction as written in the input
+    pub fn format(&self, opcode: usize, operands: &[i64]) -> String {
+        let operands = operands.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        format!("{}({})", self.instructions[opcode].name, operands.join(","))
+    }
+
+    // Number of 1 to 3 digits at the start of the input, as (value, length)
+    fn match_number(input: &[u8]) -> Match<i64> {
+        let length = input
+            .iter()
+            .take(MAX_OPERAND_DIGITS)
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        if length == input.len() {
+            // More digits or the end of the number may still follow
+            return Match::Incomplete;
+        }
+        if length == 0 {
+            return Match::None;
+        }
+        let value = input[..length]
+            .iter()
+            .fold(0, |acc, b| acc * 10 + (b - b'0') as i64);
+        Match::Found(value, length)
+    }
+
+    // `(operands)` at the start of the input, as (operands, length)
+    fn match_operands(input: &[u8], arity: &RangeInclusive<usize>) -> Match<Vec<i64>> {
+        let mut operands = vec![];
+        let mut length = 1;
+        match input.first() {
+            Some(b'(') => {}
+            Some(_) => return Match::None,
+            None => return Match::Incomplete,
+        }
+        match input.get(length) {
+            Some(b')') if arity.contains(&0) => return Match::Found(operands, length + 1),
+            Some(b')') => return Match::None,
+            Some(_) => {}
+            None => return Match::Incomplete,
+        }
+        loop {
+            let (value, value_length) = match Self::match_number(&input[length..]) {
+                Match::Found(value, value_length) => (value, value_length),
+                Match::Incomplete => return Match::Incomplete,
+                Match::None => return Match::None,
+            };
+            operands.push(value);
+            length += value_length;
+            if operands.len() > *arity.end() {
+                return Match::None;
+            }
+            match input.get(length) {
+                Some(b',') => length += 1,
+                Some(b')') => break,
+                Some(_) => return Match::None,
+                None => return Match::Incomplete,
+            }
+        }
+        match arity.contains(&operands.len()) {
+            true => Match::Found(operands, length + 1),
+            false => Match::None,
+        }
+    }
+
+    // Instruction starting exactly at the start of the input, as ((opcode, operands), length)
+    pub fn match_at(&self, input: &[u8]) -> Match<(usize, Vec<i64>)> {
+        let Some(&first) = input.first() else {
+            return Match::Incomplete;
+        };
+        let mut result = Match::None;
+        for &opcode in self.by_first_byte[first as usize].iter() {
+            let instruction = &self.instructions[opcode];
+            let name = instruction.name.as_bytes();
+            if !input.starts_with(name) {
+                if name.starts_with(input) {
+                    result = Match::Incomplete;
+                }
+                continue;
+            }
+            match Self::match_operands(&input[name.len()..], &instruction.arity) {
+                Match::Found(operands, length) => {
+                    return Match::Found((opcode, operands), name.len() + length)
+                }
+                Match::Incomplete => result = Match::Incomplete,
+                Match::None => {}
+            }
+        }
+        result
+    }
+}
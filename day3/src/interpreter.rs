@@ -0,0 +1,66 @@
+use crate::{
+    instructions::{InstructionSet, Semantics},
+    lexer::{Token, TokenKind},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Step {
+    // Value computed by the instruction, whether or not it was added to the total
+    pub value: Option<i64>,
+    // Enabled state the instruction ran under, the new state for toggles
+    pub enabled: bool,
+}
+
+#[derive(Debug)]
+pub struct Interpreter<'a> {
+    instructions: &'a InstructionSet,
+    enabled: bool,
+    // Sum of all values, and of the values produced while enabled. Each value fits an i64 but
+    // enough of them add up past it.
+    unconditional_total: i128,
+    enabled_total: i128,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(instructions: &'a InstructionSet) -> Self {
+        Interpreter {
+            instructions,
+            enabled: true,
+            unconditional_total: 0,
+            enabled_total: 0,
+        }
+    }
+
+    // Applies a token, None for junk
+    pub fn step(&mut self, token: &Token) -> Option<Step> {
+        let TokenKind::Instruction { opcode, operands } = &token.kind else {
+            return None;
+        };
+        let value = match self.instructions.get(*opcode).semantics {
+            Semantics::Value(evaluate) => {
+                let value = evaluate(operands);
+                self.unconditional_total += value as i128;
+                if self.enabled {
+                    self.enabled_total += value as i128;
+                }
+                Some(value)
+            }
+            Semantics::Toggle(toggle) => {
+                self.enabled = toggle(self.enabled);
+                None
+            }
+        };
+        Some(Step {
+            value,
+            enabled: self.enabled,
+        })
+    }
+
+    pub fn unconditional_total(&self) -> i128 {
+        self.unconditional_total
+    }
+
+    pub fn enabled_total(&self) -> i128 {
+        self.enabled_total
+    }
+}
@@ -0,0 +1,115 @@
+use crate::instructions::{InstructionSet, Match};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Instruction { opcode: usize, operands: Vec<i64> },
+    // Run of bytes that are not part of any instruction
+    Junk,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    // Byte range of the token in the whole input
+    pub start: usize,
+    pub end: usize,
+    // 1-based line and byte column of the token start
+    pub line: usize,
+    pub column: usize,
+}
+
+// Incremental lexer, input can be fed in chunks of any size and instructions split across chunk
+// boundaries are still recognized
+pub struct Lexer<'a> {
+    instructions: &'a InstructionSet,
+    // Input that could not be lexed yet because an instruction may continue in the next chunk
+    pending: Vec<u8>,
+    // Offset of the first pending byte in the whole input
+    offset: usize,
+    // Line and column of the next byte to lex
+    line: usize,
+    column: usize,
+    // Offset, line and column where the current run of junk started
+    junk_start: Option<(usize, usize, usize)>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(instructions: &'a InstructionSet) -> Self {
+        Lexer {
+            instructions,
+            pending: Vec::new(),
+            offset: 0,
+            line: 1,
+            column: 1,
+            junk_start: None,
+        }
+    }
+
+    // Tokens completed by this chunk
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Token> {
+        self.pending.extend_from_slice(chunk);
+        self.lex(false)
+    }
+
+    // Tokens left once the input has ended
+    pub fn finish(&mut self) -> Vec<Token> {
+        self.lex(true)
+    }
+
+    fn flush_junk(&mut self, end: usize, tokens: &mut Vec<Token>) {
+        if let Some((start, line, column)) = self.junk_start.take() {
+            tokens.push(Token {
+                kind: TokenKind::Junk,
+                start,
+                end,
+                line,
+                column,
+            });
+        }
+    }
+
+    fn lex(&mut self, is_final: bool) -> Vec<Token> {
+        let mut tokens = vec![];
+        let mut position = 0;
+        while position < self.pending.len() {
+            let start = self.offset + position;
+            let (line, column) = (self.line, self.column);
+            let (kind, length) = match self.instructions.match_at(&self.pending[position..]) {
+                Match::Found((opcode, operands), length) => {
+                    self.flush_junk(start, &mut tokens);
+                    (Some(TokenKind::Instruction { opcode, operands }), length)
+                }
+                // Wait for the next chunk to decide
+                Match::Incomplete if !is_final => break,
+                Match::Incomplete | Match::None => {
+                    self.junk_start.get_or_insert((start, line, column));
+                    (None, 1)
+                }
+            };
+            for &byte in &self.pending[position..position + length] {
+                if byte == b'\n' {
+                    self.line += 1;
+                    self.column = 1;
+                } else {
+                    self.column += 1;
+                }
+            }
+            position += length;
+            if let Some(kind) = kind {
+                tokens.push(Token {
+                    kind,
+                    start,
+                    end: self.offset + position,
+                    line,
+                    column,
+                });
+            }
+        }
+        self.pending.drain(..position);
+        self.offset += position;
+        if is_final {
+            self.flush_junk(self.offset, &mut tokens);
+        }
+        tokens
+    }
+}
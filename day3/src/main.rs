@@ -1,5 +1,14 @@
-use std::io::BufRead;
-use regex::Regex;
+use std::io::Read;
+
+mod instructions;
+mod interpreter;
+mod lexer;
+mod regions;
+mod trace;
+use instructions::InstructionSet;
+use interpreter::Interpreter;
+use lexer::{Lexer, TokenKind};
+use regions::RegionMap;
 
 macro_rules! dprintln {
     ($($arg:tt)*) => {
@@ -10,45 +19,111 @@ macro_rules! dprintln {
     }
 }
 
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
 
 fn main() {
-    let mut input = String::new();
+    // --extended adds variadic mul, add, sub and toggle() to the instruction set
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let instructions = match args.iter().any(|a| a == "--extended") {
+        true => InstructionSet::extended(),
+        false => InstructionSet::standard(),
+    };
+
+    // --chunk-size N sets how many bytes are read from stdin at a time
+    let chunk_size = args
+        .iter()
+        .position(|a| a == "--chunk-size")
+        .map(|i| args.get(i + 1).expect("Missing chunk size").parse::<usize>().unwrap())
+        .unwrap_or(DEFAULT_CHUNK_SIZE);
+    // Reading into an empty buffer returns 0 bytes, which looks like the end of the input
+    assert!(chunk_size > 0, "Chunk size must be at least 1");
+
+    // --trace prints every recognized instruction with its location and effect
+    let trace = args.iter().any(|a| a == "--trace");
+    // --render prints the input with instructions highlighted, which keeps the whole input in memory
+    let render = args.iter().any(|a| a == "--render");
+    // --regions prints the enabled and disabled regions with their statistics
+    let print_regions = args.iter().any(|a| a == "--regions");
+    // --enabled-at N tells whether the byte at offset N was enabled, can be repeated
+    let queried_offsets = args
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| *a == "--enabled-at")
+        .map(|(i, _)| args.get(i + 1).expect("Missing offset").parse::<usize>().unwrap())
+        .collect::<Vec<_>>();
+    let mut regions = RegionMap::new();
+    let mut source = vec![];
+    let mut rendered_tokens = vec![];
+
+    let mut interpreter = Interpreter::new(&instructions);
+    let mut lexer = Lexer::new(&instructions);
+    let mut chunk = vec![0u8; chunk_size];
     let stdin = std::io::stdin();
     {
-        let lock = stdin.lock();
+        let mut lock = stdin.lock();
 
-        // Read whole input from stdin
-        for line in lock.lines() {
-            input.push_str(&line.unwrap());
+        // Read raw bytes so line breaks stay part of the memory
+        loop {
+            let read = lock.read(&mut chunk).unwrap();
+            if render {
+                source.extend_from_slice(&chunk[..read]);
+            }
+            let tokens = match read {
+                0 => lexer.finish(),
+                _ => lexer.feed(&chunk[..read]),
+            };
+            for token in tokens {
+                if token.kind != TokenKind::Junk {
+                    dprintln!("Token at {}..{}: {:?}", token.start, token.end, token.kind);
+                }
+                let step = interpreter.step(&token);
+                regions.record(&token, step.as_ref());
+                if let (true, Some(step)) = (trace, &step) {
+                    println!("{}", trace::trace_line(&instructions, &token, step));
+                }
+                if render {
+                    rendered_tokens.push((token, step));
+                }
+            }
+            if read == 0 {
+                break;
+            }
         }
     }
-    dprintln!("input: {:?}", input);
-
-    let re = Regex::new(r"mul\((\d{1,3}),(\d{1,3})\)").unwrap();
-
-    let result: i32 = re.captures_iter(&input)
-        .filter(|c| {
-            let capture = c.get(0).unwrap();
-            let do_index = input[..capture.start()].rfind("do()").unwrap_or(usize::MAX);
-            let dont_index = input[..capture.start()].rfind("don't()").unwrap_or(usize::MAX);
-            
-            dprintln!("do_index: {}, dont_index: {}", do_index, dont_index);
-            match (dont_index == usize::MAX, do_index == usize::MAX) {
-                (true, _) => true,
-                (false, true) => false,
-                (false, false) if do_index > dont_index => true,
-                (false, false) => false,
+    if render {
+        println!("{}", trace::render(&source, &rendered_tokens));
+    }
+    if print_regions {
+        let mut disabled_bytes = 0;
+        let mut total_bytes = 0;
+        for region in regions.regions() {
+            let state = match region.enabled {
+                true => "enabled",
+                false => "disabled",
+            };
+            println!(
+                "{}..{} {}: {} instructions, sum {}",
+                region.range.start, region.range.end, state, region.instructions, region.sum
+            );
+            total_bytes += region.range.len();
+            if !region.enabled {
+                disabled_bytes += region.range.len();
             }
-        })
-        .map(|c| {
-            dprintln!("Capture: {:?}", c);
-            let a = c.get(1).unwrap().as_str().parse::<i32>().unwrap();
-            let b = c.get(2).unwrap().as_str().parse::<i32>().unwrap();
-            return [a, b]
-        })
-        .map(|[a, b]| a * b)
-        .sum();
-
-    println!("Result: {}", result);
+        }
+        println!("Disabled bytes: {} of {}", disabled_bytes, total_bytes);
+    }
+    for offset in queried_offsets {
+        match regions.is_enabled_at(offset) {
+            Some(true) => println!("Offset {}: enabled", offset),
+            Some(false) => println!("Offset {}: disabled", offset),
+            None => println!("Offset {}: past the end of the input", offset),
+        }
+    }
+
+    #[cfg(feature = "part1")]
+    println!("Result: {}", interpreter.unconditional_total());
+
+    #[cfg(feature = "part2")]
+    println!("Result: {}", interpreter.enabled_total());
 
 }
@@ -0,0 +1,71 @@
+use std::ops::Range;
+
+use crate::{interpreter::Step, lexer::Token};
+
+#[derive(Debug, Clone)]
+pub struct Region {
+    // Byte range, starting at the toggle that opened the region
+    pub range: Range<usize>,
+    pub enabled: bool,
+    // Value producing instructions in the region and the sum of their values
+    pub instructions: usize,
+    pub sum: i128,
+}
+
+// Enabled and disabled regions of the input, built as tokens are interpreted
+#[derive(Debug)]
+pub struct RegionMap {
+    regions: Vec<Region>,
+}
+
+impl RegionMap {
+    pub fn new() -> Self {
+        RegionMap {
+            regions: vec![Region {
+                range: 0..0,
+                enabled: true,
+                instructions: 0,
+                sum: 0,
+            }],
+        }
+    }
+
+    pub fn record(&mut self, token: &Token, step: Option<&Step>) {
+        let current = self.regions.last_mut().unwrap();
+        match step {
+            Some(Step {
+                value: Some(value), ..
+            }) => {
+                current.instructions += 1;
+                current.sum += *value as i128;
+            }
+            // Only toggles that change the state split regions
+            Some(Step {
+                value: None,
+                enabled,
+            }) if *enabled != current.enabled => {
+                self.regions.push(Region {
+                    range: token.start..token.start,
+                    enabled: *enabled,
+                    instructions: 0,
+                    sum: 0,
+                });
+            }
+            _ => {}
+        }
+        self.regions.last_mut().unwrap().range.end = token.end;
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    // Whether the byte at the offset was in an enabled region, None past the end of the input
+    pub fn is_enabled_at(&self, offset: usize) -> Option<bool> {
+        let index = self.regions.partition_point(|r| r.range.end <= offset);
+        self.regions
+            .get(index)
+            .filter(|r| r.range.contains(&offset))
+            .map(|r| r.enabled)
+    }
+}
@@ -0,0 +1,51 @@
+use crate::{
+    instructions::InstructionSet,
+    interpreter::Step,
+    lexer::{Token, TokenKind},
+};
+
+const HIGHLIGHT: &str = "\x1b[1;32m";
+const TOGGLE: &str = "\x1b[1;36m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+// One line describing an executed instruction
+pub fn trace_line(instructions: &InstructionSet, token: &Token, step: &Step) -> String {
+    let TokenKind::Instruction { opcode, operands } = &token.kind else {
+        unreachable!("Junk is never traced");
+    };
+    let instruction = instructions.format(*opcode, operands);
+    let state = match step.enabled {
+        true => "enabled",
+        false => "disabled",
+    };
+    match step.value {
+        Some(value) => format!(
+            "{} {}:{} {} = {} {}",
+            token.start, token.line, token.column, instruction, value, state
+        ),
+        None => format!(
+            "{} {}:{} {} -> {}",
+            token.start, token.line, token.column, instruction, state
+        ),
+    }
+}
+
+// Source with counted instructions highlighted, toggles marked and disabled instructions dimmed
+pub fn render(source: &[u8], tokens: &[(Token, Option<Step>)]) -> String {
+    let mut result = String::new();
+    for (token, step) in tokens {
+        let text = String::from_utf8_lossy(&source[token.start..token.end]);
+        let style = match step {
+            None => None,
+            Some(Step { value: None, .. }) => Some(TOGGLE),
+            Some(Step { enabled: true, .. }) => Some(HIGHLIGHT),
+            Some(Step { enabled: false, .. }) => Some(DIM),
+        };
+        match style {
+            Some(style) => result.push_str(&format!("{}{}{}", style, text, RESET)),
+            None => result.push_str(&text),
+        }
+    }
+    result
+}
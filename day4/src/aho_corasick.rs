@@ -0,0 +1,176 @@
+use std::{collections::VecDeque, marker::PhantomData};
+
+const ROOT: u32 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    pub word: usize,
+    // Position in the haystack one past the last symbol of the word
+    pub end: usize,
+}
+
+// Multi-pattern matcher, finds every occurrence of every word in one pass over the haystack
+#[derive(Debug)]
+pub struct AhoCorasick<T> {
+    symbol: PhantomData<T>,
+    // Class of each symbol value, symbols that appear in no word are class 0 and lead back to the root
+    classes: Vec<usize>,
+    class_count: usize,
+    // Complete transition table, one row of `class_count` states per state
+    transitions: Vec<u32>,
+    // Words ending at each state, without the ones reached through suffix links
+    outputs: Vec<Vec<usize>>,
+    // Closest state on the suffix link chain that ends a word
+    output_links: Vec<Option<u32>>,
+    // Words ending at each state, including the ones reached through suffix links
+    match_counts: Vec<usize>,
+    word_lengths: Vec<usize>,
+}
+
+// Symbols are looked up by value in a dense table, so they should be small integers
+impl<T: Copy + Into<usize>> AhoCorasick<T> {
+    pub fn new(words: &[Vec<T>]) -> Self {
+        let mut classes = vec![];
+        let mut class_count = 1;
+        for &symbol in words.iter().flatten() {
+            let symbol = symbol.into();
+            if classes.len() <= symbol {
+                classes.resize(symbol + 1, 0);
+            }
+            if classes[symbol] == 0 {
+                classes[symbol] = class_count;
+                class_count += 1;
+            }
+        }
+
+        // Build the trie, 0 marks a missing edge since the root is never a child
+        let mut transitions = vec![ROOT; class_count];
+        let mut outputs = vec![vec![]];
+        for (word, symbols) in words.iter().enumerate() {
+            if symbols.is_empty() {
+                continue;
+            }
+            let mut state = ROOT;
+            for symbol in symbols {
+                let edge = state as usize * class_count + classes[(*symbol).into()];
+                if transitions[edge] == ROOT {
+                    transitions[edge] = outputs.len() as u32;
+                    transitions.extend(std::iter::repeat_n(ROOT, class_count));
+                    outputs.push(vec![]);
+                }
+                state = transitions[edge];
+            }
+            outputs[state as usize].push(word);
+        }
+
+        // Turn the trie into a complete automaton breadth first, so each state's suffix link is
+        // already final when its children are visited
+        let state_count = outputs.len();
+        let mut suffix_links = vec![ROOT; state_count];
+        let mut output_links = vec![None; state_count];
+        let mut match_counts = outputs.iter().map(Vec::len).collect::<Vec<_>>();
+        let mut queue = VecDeque::new();
+        queue.extend(transitions[1..class_count].iter().copied().filter(|&child| child != ROOT));
+        while let Some(state) = queue.pop_front() {
+            let link = suffix_links[state as usize];
+            for class in 1..class_count {
+                let edge = state as usize * class_count + class;
+                let fallback = transitions[link as usize * class_count + class];
+                let child = transitions[edge];
+                if child == ROOT {
+                    transitions[edge] = fallback;
+                } else {
+                    suffix_links[child as usize] = fallback;
+                    match_counts[child as usize] += match_counts[fallback as usize];
+                    output_links[child as usize] = match outputs[fallback as usize].is_empty() {
+                        true => output_links[fallback as usize],
+                        false => Some(fallback),
+                    };
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        AhoCorasick {
+            symbol: PhantomData,
+            classes,
+            class_count,
+            transitions,
+            outputs,
+            output_links,
+            match_counts,
+            word_lengths: words.iter().map(|w| w.len()).collect(),
+        }
+    }
+
+    pub fn word_length(&self, word: usize) -> usize {
+        self.word_lengths[word]
+    }
+
+    pub fn max_word_length(&self) -> usize {
+        self.word_lengths.iter().copied().max().unwrap_or(0)
+    }
+
+    fn next_state(&self, state: u32, symbol: T) -> u32 {
+        let class = self.classes.get(symbol.into()).copied().unwrap_or(0);
+        self.transitions[state as usize * self.class_count + class]
+    }
+
+    // Every occurrence, in order of their end position
+    pub fn find_iter<I: IntoIterator<Item = T>>(&self, haystack: I) -> FindIter<'_, T, I::IntoIter> {
+        FindIter {
+            automaton: self,
+            haystack: haystack.into_iter(),
+            position: 0,
+            state: ROOT,
+            output: None,
+            next_word: 0,
+        }
+    }
+
+    // Number of occurrences, without looking at which words they are
+    pub fn count<I: IntoIterator<Item = T>>(&self, haystack: I) -> usize {
+        let mut state = ROOT;
+        let mut count = 0;
+        for symbol in haystack {
+            state = self.next_state(state, symbol);
+            count += self.match_counts[state as usize];
+        }
+        count
+    }
+}
+
+pub struct FindIter<'a, T, I> {
+    automaton: &'a AhoCorasick<T>,
+    haystack: I,
+    // Symbols consumed so far
+    position: usize,
+    state: u32,
+    // State on the output link chain whose words are being reported, and the next of its words
+    output: Option<u32>,
+    next_word: usize,
+}
+
+impl<T: Copy + Into<usize>, I: Iterator<Item = T>> Iterator for FindIter<'_, T, I> {
+    type Item = Match;
+
+    fn next(&mut self) -> Option<Match> {
+        loop {
+            while let Some(output) = self.output {
+                if let Some(&word) = self.automaton.outputs[output as usize].get(self.next_word) {
+                    self.next_word += 1;
+                    return Some(Match {
+                        word,
+                        end: self.position,
+                    });
+                }
+                self.output = self.automaton.output_links[output as usize];
+                self.next_word = 0;
+            }
+            let symbol = self.haystack.next()?;
+            self.state = self.automaton.next_state(self.state, symbol);
+            self.position += 1;
+            self.output = Some(self.state);
+        }
+    }
+}
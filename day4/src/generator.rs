@@ -0,0 +1,169 @@
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+
+use crate::{
+    symbols::{Symbols, HOLE},
+    FoundIdent, WordSearchLine, WordSearchLineIdent, WordSearchMatrix, DIRECTIONS,
+};
+
+// Grids tried before giving up, and positions tried for each word in a grid
+const GRID_ATTEMPTS: usize = 100;
+const PLACEMENT_ATTEMPTS: usize = 1000;
+
+#[derive(Debug, Clone)]
+pub struct Placement {
+    pub word: usize,
+    pub ident: FoundIdent,
+}
+
+// Buffer indices of the cells of a word starting at (x, y), None when it does not fit in the grid.
+// On a torus the start wraps around and the word must not run into itself.
+fn word_cells(matrix: &WordSearchMatrix, x: isize, y: isize, x_dir: i8, y_dir: i8, length: usize) -> Option<Vec<usize>> {
+    let (width, height) = (matrix.width as isize, matrix.height as isize);
+    let (x, y) = match matrix.wrap {
+        true if length <= matrix.cycle_length(x_dir, y_dir) => (x.rem_euclid(width), y.rem_euclid(height)),
+        true => return None,
+        false => {
+            let end_x = x + (length as isize - 1) * x_dir as isize;
+            let end_y = y + (length as isize - 1) * y_dir as isize;
+            let inside = |x: isize, y: isize| (0..width).contains(&x) && (0..height).contains(&y);
+            if !inside(x, y) || !inside(end_x, end_y) {
+                return None;
+            }
+            (x, y)
+        }
+    };
+    let line = WordSearchLine {
+        ident: WordSearchLineIdent {
+            x: x as usize,
+            y: y as usize,
+            x_dir,
+            y_dir,
+        },
+        cycle: length,
+        length,
+        wrap: matrix.wrap,
+    };
+    Some(line.indices(matrix).collect())
+}
+
+// Whether the cell holds a letter matching the symbol
+fn matches(matrix: &WordSearchMatrix, index: usize, symbol: u16) -> bool {
+    matrix.cells[index] != HOLE && matrix.symbols.key(matrix.cells[index]) == matrix.symbols.key(symbol)
+}
+
+// Writes the word at a random position and direction where it only crosses matching letters
+fn place(
+    matrix: &mut WordSearchMatrix,
+    word: usize,
+    symbols: &[u16],
+    directions: &[(i8, i8)],
+    rng: &mut StdRng,
+) -> Option<Placement> {
+    for _ in 0..PLACEMENT_ATTEMPTS {
+        let &(x_dir, y_dir) = directions.choose(rng)?;
+        let x = rng.gen_range(0..matrix.width);
+        let y = rng.gen_range(0..matrix.height);
+        let Some(cells) = word_cells(matrix, x as isize, y as isize, x_dir, y_dir, symbols.len()) else {
+            continue;
+        };
+        let fits = cells
+            .iter()
+            .zip(symbols)
+            .all(|(&i, &symbol)| matrix.cells[i] == HOLE || matches(matrix, i, symbol));
+        // A word lying entirely on letters that are already placed adds nothing to the puzzle
+        let adds = cells.iter().any(|&i| matrix.cells[i] == HOLE);
+        if fits && adds {
+            for (&i, &symbol) in cells.iter().zip(symbols) {
+                matrix.cells[i] = symbol;
+            }
+            return Some(Placement {
+                word,
+                ident: FoundIdent { y, x, y_dir, x_dir },
+            });
+        }
+    }
+    None
+}
+
+// Whether any word in any direction runs through (x, y)
+fn completes_word(matrix: &WordSearchMatrix, x: usize, y: usize, words: &[Vec<u16>]) -> bool {
+    DIRECTIONS.into_iter().any(|(x_dir, y_dir)| {
+        words.iter().any(|word| {
+            (0..word.len() as isize).any(|k| {
+                let start_x = x as isize - k * x_dir as isize;
+                let start_y = y as isize - k * y_dir as isize;
+                word_cells(matrix, start_x, start_y, x_dir, y_dir, word.len())
+                    .is_some_and(|cells| cells.iter().zip(word).all(|(&i, &symbol)| matches(matrix, i, symbol)))
+            })
+        })
+    })
+}
+
+// Fills the holes one at a time, a hole only completes occurrences running through it, so checking
+// those keeps the filler from adding any. Letters of the words are preferred so that the puzzle
+// does not give the words away, other letters are only used when none of those fit.
+fn fill(matrix: &mut WordSearchMatrix, words: &[Vec<u16>], others: &[u16], rng: &mut StdRng) -> bool {
+    let mut letters = words.iter().flatten().copied().collect::<Vec<_>>();
+    letters.sort();
+    letters.dedup();
+    let mut others = others.to_vec();
+    for y in 0..matrix.height {
+        for x in 0..matrix.width {
+            if matrix.cells[matrix.index(x, y)] != HOLE {
+                continue;
+            }
+            letters.shuffle(rng);
+            others.shuffle(rng);
+            let fits = letters.iter().chain(others.iter()).any(|&letter| {
+                matrix.set(x, y, letter);
+                !completes_word(matrix, x, y, words)
+            });
+            if !fits {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+// Puzzle with every word placed at least once, placing the longest words first while the grid is
+// still empty
+pub fn generate(
+    width: usize,
+    height: usize,
+    wrap: bool,
+    mut symbols: Symbols,
+    words: &[Vec<u16>],
+    directions: &[(i8, i8)],
+    rng: &mut StdRng,
+) -> Option<(WordSearchMatrix, Vec<Placement>)> {
+    assert!(
+        words.iter().flatten().all(|&symbol| symbols.is_letter(symbol)),
+        "Words can only contain letters"
+    );
+    let keys = words.iter().flatten().map(|&symbol| symbols.key(symbol)).collect::<Vec<_>>();
+    let mut others = (b'A'..=b'Z')
+        .map(|c| symbols.intern(&(c as char).to_string()))
+        .collect::<Vec<_>>();
+    others.retain(|&symbol| !keys.contains(&symbols.key(symbol)));
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let mut order = (0..words.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&word| std::cmp::Reverse(words[word].len()));
+    'attempt: for _ in 0..GRID_ATTEMPTS {
+        let mut matrix = WordSearchMatrix::empty(width, height, wrap, symbols.clone());
+        let mut placements = vec![];
+        for &word in order.iter() {
+            match place(&mut matrix, word, &words[word], directions, rng) {
+                Some(placement) => placements.push(placement),
+                None => continue 'attempt,
+            }
+        }
+        if fill(&mut matrix, words, &others, rng) {
+            placements.sort_by_key(|p| p.word);
+            return Some((matrix, placements));
+        }
+    }
+    None
+}
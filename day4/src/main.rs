@@ -1,4 +1,17 @@
-use std::{collections::HashSet, io::BufRead};
+use itertools::Either;
+use rand::{rngs::StdRng, SeedableRng};
+use rayon::prelude::*;
+use std::io::BufRead;
+
+mod aho_corasick;
+mod generator;
+mod render;
+mod symbols;
+mod template;
+use aho_corasick::AhoCorasick;
+use render::RenderMode;
+use symbols::{Normalization, Symbols, HOLE};
+use template::{Template, TemplateMatch, Variant};
 
 macro_rules! dprintln {
     ($($arg:tt)*) => {
@@ -9,136 +22,328 @@ macro_rules! dprintln {
     }
 }
 
+const DIRECTIONS: [(i8, i8); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+];
+
+fn direction_name(x_dir: i8, y_dir: i8) -> &'static str {
+    match (x_dir, y_dir) {
+        (1, 0) => "right",
+        (-1, 0) => "left",
+        (0, 1) => "down",
+        (0, -1) => "up",
+        (1, 1) => "down-right",
+        (-1, -1) => "up-left",
+        (1, -1) => "up-right",
+        (-1, 1) => "down-left",
+        _ => unreachable!(),
+    }
+}
+
+fn parse_direction(name: &str) -> (i8, i8) {
+    DIRECTIONS
+        .into_iter()
+        .find(|&(x_dir, y_dir)| direction_name(x_dir, y_dir) == name)
+        .unwrap_or_else(|| panic!("Unknown direction {}", name))
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+// Letters stored row after row in one buffer, cell (x, y) is at `y * width + x`. Each cell is one
+// grapheme, interned in `symbols`.
 #[derive(Debug)]
 struct WordSearchMatrix {
-    matrix: Vec<Vec<char>>,
+    cells: Vec<u16>,
+    symbols: Symbols,
+    width: usize,
+    height: usize,
+    // Letters in each row, rows are padded with holes to the full width by `finish`
+    row_lengths: Vec<usize>,
+    // Lines continue on the opposite edge, as on a torus
+    wrap: bool,
 }
 
 impl WordSearchMatrix {
-    fn new() -> Self {
-        WordSearchMatrix { matrix: Vec::new() }
+    fn new(wrap: bool, normalization: Normalization) -> Self {
+        WordSearchMatrix {
+            cells: Vec::new(),
+            symbols: Symbols::new(normalization),
+            width: 0,
+            height: 0,
+            row_lengths: Vec::new(),
+            wrap,
+        }
     }
 
     fn add_row(&mut self, row: String) {
-        self.matrix
-            .push(row.chars().filter(char::is_ascii_alphabetic).collect());
-    }
-
-    fn get_search_line(&self, x_dir: i8, y_dir: i8) -> Vec<WordSearchLine> {
-        let mut lines = Vec::new();
-        let x_bounds = 0..self.matrix[0].len();
-        let y_bounds = 0..self.matrix.len();
-
-        // Iterate over the edges
-        if x_dir != 0 {
-            for start_y in y_bounds.clone() {
-                let mut line = Vec::new();
-                let mut x = if x_dir == 1 {
-                    0
-                } else {
-                    self.matrix[0].len() - 1
-                } as i64;
-                let mut y = start_y as i64;
-                let ident = WordSearchLineIdent {
-                    x: x as usize,
-                    y: y as usize,
-                    x_dir,
-                    y_dir,
-                };
+        let start = self.cells.len();
+        self.symbols.intern_row(&row, &mut self.cells);
+        let length = self.cells.len() - start;
+        self.width = self.width.max(length);
+        self.row_lengths.push(length);
+        self.height += 1;
+    }
 
-                while x_bounds.contains(&(x as usize)) && y_bounds.contains(&(y as usize)) {
-                    line.push(&self.matrix[y as usize][x as usize]);
-                    x += x_dir as i64;
-                    y += y_dir as i64;
-                }
+    // Grid of holes to be filled in
+    fn empty(width: usize, height: usize, wrap: bool, symbols: Symbols) -> Self {
+        WordSearchMatrix {
+            cells: vec![HOLE; width * height],
+            symbols,
+            width,
+            height,
+            row_lengths: vec![width; height],
+            wrap,
+        }
+    }
 
-                if !line.is_empty() {
-                    lines.push(WordSearchLine::new(line, ident));
-                }
-            }
+    // Pads the rows in place, moving them from the last one so that no row is overwritten before
+    // it has been moved
+    fn finish(&mut self) {
+        self.cells.resize(self.width * self.height, HOLE);
+        let mut start = self.row_lengths.iter().sum::<usize>();
+        for (y, &length) in self.row_lengths.iter().enumerate().rev() {
+            start -= length;
+            let row = y * self.width;
+            self.cells.copy_within(start..start + length, row);
+            self.cells[row + length..row + self.width].fill(HOLE);
         }
+    }
 
-        if y_dir != 0 {
-            for start_x in x_bounds.clone() {
-                let mut line = Vec::new();
-                let mut x = start_x as i64;
-                let mut y = if y_dir == 1 { 0 } else { self.matrix.len() - 1 } as i64;
-                let ident = WordSearchLineIdent {
-                    x: x as usize,
-                    y: y as usize,
-                    x_dir,
-                    y_dir,
-                };
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
 
-                while x_bounds.contains(&(x as usize)) && y_bounds.contains(&(y as usize)) {
-                    line.push(&self.matrix[y as usize][x as usize]);
-                    x += x_dir as i64;
-                    y += y_dir as i64;
-                }
+    // Number of cells from (x, y) to the edge of the grid, walking in the given direction
+    fn steps_to_edge(&self, x: usize, y: usize, x_dir: i8, y_dir: i8) -> usize {
+        let steps = |position: usize, dir: i8, size: usize| match dir {
+            1 => size - position,
+            -1 => position + 1,
+            _ => usize::MAX,
+        };
+        steps(x, x_dir, self.width).min(steps(y, y_dir, self.height))
+    }
 
-                if !line.is_empty() {
-                    lines.push(WordSearchLine::new(line, ident));
+    // Every line in a direction starts on the edge it is walking away from, diagonals starting in
+    // the corner are only taken from the column edge. Each cell is on exactly one line per
+    // direction, so every occurrence is seen exactly once and counts need no deduplication.
+    fn get_search_line(&self, x_dir: i8, y_dir: i8, overlap: usize) -> impl Iterator<Item = WordSearchLine> + '_ {
+        if self.wrap {
+            return Either::Right(self.get_wrapped_search_line(x_dir, y_dir, overlap));
+        }
+        let start_x = if x_dir == 1 { 0 } else { self.width.saturating_sub(1) };
+        let start_y = if y_dir == 1 { 0 } else { self.height.saturating_sub(1) };
+        let column_edge = (0..self.height)
+            .filter(move |_| x_dir != 0)
+            .map(move |y| (start_x, y));
+        let row_edge = (0..self.width)
+            .filter(move |&x| y_dir != 0 && (x_dir == 0 || x != start_x))
+            .map(move |x| (x, start_y));
+        let lines = column_edge
+            .chain(row_edge)
+            .filter(|_| self.width > 0 && self.height > 0)
+            .map(move |(x, y)| {
+                let length = self.steps_to_edge(x, y, x_dir, y_dir);
+                WordSearchLine {
+                    ident: WordSearchLineIdent { x, y, x_dir, y_dir },
+                    cycle: length,
+                    length,
+                    wrap: false,
                 }
-            }
+            });
+        Either::Left(lines)
+    }
+
+    // Cells on a wrapped line in the given direction before it returns to its start
+    fn cycle_length(&self, x_dir: i8, y_dir: i8) -> usize {
+        match (x_dir, y_dir) {
+            (_, 0) => self.width,
+            (0, _) => self.height,
+            _ => self.width * self.height / gcd(self.width, self.height).max(1),
         }
-        lines
     }
 
-    fn get_search_lines(&self) -> Vec<WordSearchLine> {
-        [
-            (1, 0),
-            (-1, 0),
-            (0, 1),
-            (0, -1),
-            (1, 1),
-            (-1, -1),
-            (1, -1),
-            (-1, 1),
-        ]
-        .into_iter()
-        .map(|(x, y)| self.get_search_line(x, y))
-        .flatten()
-        .collect()
-    }
-
-    fn get_sub_grids(&self) -> Vec<XSubGrid> {
-        let mut sub_grids = Vec::new();
-        for y in 0..self.matrix.len() - 2 {
-            for x in 0..self.matrix[0].len() - 2 {
-                let grid = [
-                    [&self.matrix[y][x], &self.matrix[y][x + 1], &self.matrix[y][x + 2]],
-                    [&self.matrix[y + 1][x], &self.matrix[y + 1][x + 1], &self.matrix[y + 1][x + 2]],
-                    [&self.matrix[y + 2][x], &self.matrix[y + 2][x + 1], &self.matrix[y + 2][x + 2]],
-                ];
-                sub_grids.push(XSubGrid { grid });
-            }
+    // On a torus every line is a cycle, rows and columns are one cycle each and the diagonals of a
+    // width x height grid form gcd(width, height) cycles, each starting in a different cell of the
+    // top row. Cycles are scanned one word length past their end so that words crossing the
+    // starting cell are found too.
+    fn get_wrapped_search_line(&self, x_dir: i8, y_dir: i8, overlap: usize) -> impl Iterator<Item = WordSearchLine> + '_ {
+        let cycle = self.cycle_length(x_dir, y_dir);
+        let starts = match (x_dir, y_dir) {
+            (_, 0) => self.height,
+            (0, _) => self.width,
+            _ => gcd(self.width, self.height),
+        };
+        (0..starts)
+            .filter(|_| self.width > 0 && self.height > 0)
+            .map(move |start| {
+                let (x, y) = match y_dir {
+                    0 => (0, start),
+                    _ => (start, 0),
+                };
+                WordSearchLine {
+                    ident: WordSearchLineIdent { x, y, x_dir, y_dir },
+                    cycle,
+                    length: cycle + overlap,
+                    wrap: true,
+                }
+            })
+    }
+
+    // Lines in all directions, `overlap` is how far wrapped lines are scanned past their end
+    fn get_search_lines(&self, overlap: usize) -> Vec<WordSearchLine> {
+        DIRECTIONS
+            .into_iter()
+            .flat_map(|(x, y)| self.get_search_line(x, y, overlap))
+            .collect()
+    }
+
+    fn set(&mut self, x: usize, y: usize, letter: u16) {
+        let index = self.index(x, y);
+        self.cells[index] = letter;
+    }
+
+    // Grid rows as text, holes are left out
+    fn rows(&self) -> impl Iterator<Item = String> + '_ {
+        self.cells
+            .chunks(self.width.max(1))
+            .map(|row| row.iter().filter(|&&c| c != HOLE).map(|&c| self.symbols.text(c)).collect())
+    }
+
+    // Match key of the letter at (x, y), coordinates past the edges wrap around on a torus
+    fn get(&self, x: usize, y: usize) -> Option<u16> {
+        let (x, y) = match self.wrap && self.width > 0 && self.height > 0 {
+            true => (x % self.width, y % self.height),
+            false => (x, y),
+        };
+        match x < self.width && y < self.height {
+            true => Some(self.symbols.key(self.cells[self.index(x, y)])).filter(|&c| c != HOLE),
+            false => None,
         }
-        sub_grids
     }
-}
 
-struct XSubGrid<'a> {
-    grid: [[&'a char; 3]; 3],
-}
+    // Automaton matching the keys of the words' graphemes
+    fn automaton(&mut self, words: &[String]) -> AhoCorasick<u16> {
+        let words = words
+            .iter()
+            .map(|w| {
+                let ids = self.symbols.intern_all(w);
+                ids.into_iter().map(|id| self.symbols.key(id)).collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        AhoCorasick::new(&words)
+    }
+
+    // Every occurrence as (word, ident), found lazily one line at a time
+    fn matches<'a>(&'a self, automaton: &'a AhoCorasick<u16>) -> impl Iterator<Item = (usize, FoundIdent)> + 'a {
+        let overlap = automaton.max_word_length().saturating_sub(1);
+        DIRECTIONS
+            .into_iter()
+            .flat_map(move |(x, y)| self.get_search_line(x, y, overlap))
+            .flat_map(move |line| line.matches(self, automaton))
+    }
 
-impl XSubGrid<'_> {
-    fn is_x(&self) -> bool {
-        self.is_cross_diag_right() && self.is_diag_right()
+    // Number of occurrences of all words, the lines are scanned in parallel
+    fn count_matches(&self, automaton: &AhoCorasick<u16>) -> usize {
+        self.get_search_lines(automaton.max_word_length().saturating_sub(1))
+            .par_iter()
+            .map(|line| line.count(self, automaton))
+            .sum()
     }
-    fn is_diag_right(&self) -> bool {
-        match self.grid {
-            [[a, _, _], [_, b, _], [_, _, c]] => (a == &'M' && b == &'A' && c == &'S') || (a == &'S' && b == &'A' && c == &'M'),
+
+    // Occurrences of each word, the lines are scanned in parallel
+    fn find_words(&self, automaton: &AhoCorasick<u16>, word_count: usize) -> Vec<Vec<FoundIdent>> {
+        self.get_search_lines(automaton.max_word_length().saturating_sub(1))
+            .par_iter()
+            .fold(
+                || vec![vec![]; word_count],
+                |mut found, line| {
+                    for (word, ident) in line.matches(self, automaton) {
+                        found[word].push(ident);
+                    }
+                    found
+                },
+            )
+            .reduce(
+                || vec![vec![]; word_count],
+                |mut found, other| {
+                    for (found, other) in found.iter_mut().zip(other) {
+                        found.extend(other);
+                    }
+                    found
+                },
+            )
+    }
+
+    // Number of found words covering each cell
+    fn coverage(&self, found: &[Vec<FoundIdent>], automaton: &AhoCorasick<u16>) -> Vec<u32> {
+        let mut coverage = vec![0; self.cells.len()];
+        for (word, found) in found.iter().enumerate() {
+            for ident in found {
+                let line = WordSearchLine {
+                    ident: WordSearchLineIdent {
+                        x: ident.x,
+                        y: ident.y,
+                        x_dir: ident.x_dir,
+                        y_dir: ident.y_dir,
+                    },
+                    cycle: automaton.word_length(word),
+                    length: automaton.word_length(word),
+                    wrap: self.wrap,
+                };
+                for index in line.indices(self) {
+                    coverage[index] += 1;
+                }
+            }
         }
+        coverage
     }
 
-    fn is_cross_diag_right(&self) -> bool {
-        match self.grid {
-            [[_, _, a], [_, b, _], [c, _, _]] => (a == &'M' && b == &'A' && c == &'S') || (a == &'S' && b == &'A' && c == &'M'),
+    // Number of positions a template of the given size can take along an axis of the grid, on a
+    // torus every cell is a position as long as the template does not overlap itself
+    fn template_positions(&self, size: usize, extent: usize) -> usize {
+        match self.wrap {
+            true if size <= extent => extent,
+            true => 0,
+            false => (extent + 1).saturating_sub(size),
         }
     }
+
+    // Template matches by top left corner, the rows are scanned in parallel
+    fn find_templates(&self, variants: &[Variant<u16>]) -> Vec<TemplateMatch> {
+        (0..self.height)
+            .into_par_iter()
+            .flat_map_iter(|y| {
+                variants
+                    .iter()
+                    .filter(move |variant| y < self.template_positions(variant.height(), self.height))
+                    .flat_map(move |variant| {
+                        (0..self.template_positions(variant.width(), self.width))
+                            .filter(move |&x| variant.matches_at(x, y, |x, y| self.get(x, y)))
+                            .map(move |x| TemplateMatch {
+                                template: variant.template,
+                                y,
+                                x,
+                                orientation: variant.orientation,
+                            })
+                    })
+            })
+            .collect()
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 struct WordSearchLineIdent {
     x: usize,
     y: usize,
@@ -146,60 +351,294 @@ struct WordSearchLineIdent {
     y_dir: i8,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 struct FoundIdent {
-    x: usize,
     y: usize,
-    x_dir: i8,
+    x: usize,
     y_dir: i8,
+    x_dir: i8,
 }
 
-impl WordSearchLineIdent {
-    fn ident(&self, i: i32) -> FoundIdent {
-        FoundIdent {
-            x: (self.x as i32 + i * self.x_dir as i32) as usize,
-            y: (self.y as i32 + i * self.y_dir as i32) as usize,
-            x_dir: self.x_dir,
-            y_dir: self.y_dir,
-        }
+impl FoundIdent {
+    fn direction_name(&self) -> &'static str {
+        direction_name(self.x_dir, self.y_dir)
     }
 }
 
-#[derive(Debug)]
-struct WordSearchLine<'a> {
+// A line through the grid, its cells are read straight from the matrix buffer
+#[derive(Debug, Clone, Copy)]
+struct WordSearchLine {
     ident: WordSearchLineIdent,
-    line: Vec<&'a char>,
+    // Distinct cells on the line, words longer than that would reuse a cell
+    cycle: usize,
+    // Cells scanned, more than `cycle` when the line wraps around
+    length: usize,
+    wrap: bool,
 }
 
-impl<'a> WordSearchLine<'a> {
-    fn new(line: Vec<&'a char>, ident: WordSearchLineIdent) -> Self {
-        WordSearchLine { line, ident }
+impl WordSearchLine {
+    // Coordinates of the i-th cell of the line
+    fn position(&self, i: usize, matrix: &WordSearchMatrix) -> (usize, usize) {
+        let x = self.ident.x as isize + i as isize * self.ident.x_dir as isize;
+        let y = self.ident.y as isize + i as isize * self.ident.y_dir as isize;
+        match self.wrap {
+            true => (
+                x.rem_euclid(matrix.width as isize) as usize,
+                y.rem_euclid(matrix.height as isize) as usize,
+            ),
+            false => (x as usize, y as usize),
+        }
     }
 
-    fn find_xmas_ident(&self) -> Vec<FoundIdent> {
-        if self.line.len() < 4 {
-            return vec![];
+    // Positions of the line's cells in the matrix buffer
+    fn indices(&self, matrix: &WordSearchMatrix) -> LineIndices {
+        let WordSearchLineIdent { x, y, x_dir, y_dir } = self.ident;
+        if self.wrap {
+            return LineIndices::Wrapped {
+                x,
+                y,
+                x_dir,
+                y_dir,
+                width: matrix.width,
+                height: matrix.height,
+                remaining: self.length,
+            };
         }
-        let mut found = vec![];
-        let mut i = 0;
-        while i < self.line.len() - 3 {
-            match self.line[i..i + 4] {
-                [a, b, c, d] => {
-                    if a == &'X' && b == &'M' && c == &'A' && d == &'S' {
-                        found.push(self.ident.ident(i as i32));
-                        i += 3;
-                    }
-                }
-                _ => {}
+        LineIndices::Stride {
+            next: matrix.index(x, y) as isize,
+            stride: y_dir as isize * matrix.width as isize + x_dir as isize,
+            remaining: self.length,
+        }
+    }
+
+    // Match keys of the line's cells
+    fn keys<'a>(&self, matrix: &'a WordSearchMatrix) -> impl Iterator<Item = u16> + 'a {
+        self.indices(matrix).map(|index| matrix.symbols.key(matrix.cells[index]))
+    }
+
+    fn matches<'a>(
+        &self,
+        matrix: &'a WordSearchMatrix,
+        automaton: &'a AhoCorasick<u16>,
+    ) -> impl Iterator<Item = (usize, FoundIdent)> + 'a {
+        let line = *self;
+        automaton.find_iter(self.keys(matrix)).filter_map(move |m| {
+            let length = automaton.word_length(m.word);
+            let start = m.end - length;
+            // Matches starting past the cycle were already found from its first cells
+            if start >= line.cycle || length > line.cycle {
+                return None;
+            }
+            let (x, y) = line.position(start, matrix);
+            let ident = FoundIdent {
+                x,
+                y,
+                x_dir: line.ident.x_dir,
+                y_dir: line.ident.y_dir,
+            };
+            Some((m.word, ident))
+        })
+    }
+
+    // Number of matches, lines that do not wrap have no repeated cells to skip, so the automaton
+    // counts them without reporting each one
+    fn count(&self, matrix: &WordSearchMatrix, automaton: &AhoCorasick<u16>) -> usize {
+        match self.wrap {
+            true => self.matches(matrix, automaton).count(),
+            false => automaton.count(self.keys(matrix)),
+        }
+    }
+}
+
+// Buffer indices of the cells of a line, a line that does not wrap is a fixed stride through the
+// buffer and a wrapped one steps cell by cell, jumping to the opposite edge instead of dividing
+enum LineIndices {
+    Stride {
+        next: isize,
+        stride: isize,
+        remaining: usize,
+    },
+    Wrapped {
+        x: usize,
+        y: usize,
+        x_dir: i8,
+        y_dir: i8,
+        width: usize,
+        height: usize,
+        remaining: usize,
+    },
+}
+
+impl Iterator for LineIndices {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        match self {
+            LineIndices::Stride { remaining: 0, .. } | LineIndices::Wrapped { remaining: 0, .. } => None,
+            LineIndices::Stride { next, stride, remaining } => {
+                let index = *next as usize;
+                *next += *stride;
+                *remaining -= 1;
+                Some(index)
+            }
+            LineIndices::Wrapped { x, y, x_dir, y_dir, width, height, remaining } => {
+                let index = *y * *width + *x;
+                let step = |position: usize, dir: i8, size: usize| match dir {
+                    1 if position + 1 == size => 0,
+                    1 => position + 1,
+                    -1 if position == 0 => size - 1,
+                    -1 => position - 1,
+                    _ => position,
+                };
+                *x = step(*x, *x_dir, *width);
+                *y = step(*y, *y_dir, *height);
+                *remaining -= 1;
+                Some(index)
             }
-            i += 1;
         }
-        found
+    }
+}
+
+// Words from --words <file> (one per line) and --word <word>, XMAS when none are given
+fn read_word_list(args: &[String]) -> Vec<String> {
+    let mut words = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--words" => {
+                let path = args.next().expect("Missing word list path");
+                let list = std::fs::read_to_string(path)
+                    .unwrap_or_else(|e| panic!("Failed to read word list {}: {}", path, e));
+                words.extend(list.lines().map(str::trim).filter(|w| !w.is_empty()).map(String::from));
+            }
+            "--word" => words.push(args.next().expect("Missing word").clone()),
+            _ => {}
+        }
+    }
+    if words.is_empty() {
+        words.push("XMAS".to_string());
+    }
+    words
+}
+
+// Templates from --template <file>, matched in the orientations allowed by --rotations and
+// --reflections, X-MAS in all rotations when none are given
+fn read_templates(args: &[String]) -> (Vec<Template>, Vec<Variant<String>>) {
+    let wildcard = args
+        .iter()
+        .position(|a| a == "--wildcard")
+        .map(|i| args.get(i + 1).expect("Missing wildcard").chars().next().expect("Empty wildcard"))
+        .unwrap_or('.');
+    let templates = args
+        .iter()
+        .enumerate()
+        .filter(|(_, a)| *a == "--template")
+        .flat_map(|(i, _)| Template::load(args.get(i + 1).expect("Missing template path"), wildcard))
+        .collect::<Vec<_>>();
+    if templates.is_empty() {
+        let variants = Template::x_mas().variants(0, true, false);
+        return (vec![Template::x_mas()], variants);
+    }
+    let rotations = args.iter().any(|a| a == "--rotations");
+    let reflections = args.iter().any(|a| a == "--reflections");
+    let variants = templates
+        .iter()
+        .enumerate()
+        .flat_map(|(i, t)| t.variants(i, rotations, reflections))
+        .collect();
+    (templates, variants)
+}
+
+// Occurrences of each word, sorted by position
+fn format_found(words: &[String], found: Vec<Vec<FoundIdent>>) -> String {
+    let mut result = String::new();
+    for (word, mut found) in words.iter().zip(found) {
+        found.sort();
+        result.push_str(&format!("{}: {}\n", word, found.len()));
+        for ident in found {
+            result.push_str(&format!("  ({}, {}) {}\n", ident.x, ident.y, ident.direction_name()));
+        }
+    }
+    result
+}
+
+// --generate <width>x<height> builds a puzzle from the word list instead of solving one, placing the
+// words in the --directions given as a comma separated list (all eight by default). --seed makes
+// the puzzle reproducible and --key <file> writes the answer key there instead of after the puzzle.
+fn generate(args: &[String], words: &[String], size: &str, wrap: bool, normalization: Normalization) {
+    let (width, height) = size
+        .split_once('x')
+        .map(|(w, h)| (w.parse().expect("Invalid width"), h.parse().expect("Invalid height")))
+        .expect("Size must be <width>x<height>");
+    let directions = args
+        .iter()
+        .position(|a| a == "--directions")
+        .map(|i| {
+            let list = args.get(i + 1).expect("Missing directions");
+            list.split(',').map(parse_direction).collect()
+        })
+        .unwrap_or(DIRECTIONS.to_vec());
+    let mut rng = match args.iter().position(|a| a == "--seed") {
+        Some(i) => StdRng::seed_from_u64(args.get(i + 1).expect("Missing seed").parse().expect("Invalid seed")),
+        None => StdRng::from_entropy(),
+    };
+    let key_path = args.iter().position(|a| a == "--key").map(|i| args.get(i + 1).expect("Missing key path"));
+
+    let mut symbols = Symbols::new(normalization);
+    let word_symbols = words.iter().map(|w| symbols.intern_all(w)).collect::<Vec<_>>();
+    let (mut matrix, placements) = generator::generate(width, height, wrap, symbols, &word_symbols, &directions, &mut rng)
+        .unwrap_or_else(|| panic!("Could not fit the words into a {}x{} grid", width, height));
+
+    // Round trip, every placed word has to be found again
+    let automaton = matrix.automaton(words);
+    let found = matrix.find_words(&automaton, words.len());
+    for placement in placements.iter() {
+        assert!(
+            found[placement.word].contains(&placement.ident),
+            "Placed {} at ({}, {}) {} but it was not found",
+            words[placement.word],
+            placement.ident.x,
+            placement.ident.y,
+            placement.ident.direction_name()
+        );
+    }
+
+    for row in matrix.rows() {
+        println!("{}", row);
+    }
+    let key = format_found(words, found);
+    match key_path {
+        Some(path) => std::fs::write(path, key).unwrap_or_else(|e| panic!("Failed to write key {}: {}", path, e)),
+        None => print!("\n{}", key),
     }
 }
 
 fn main() {
-    let mut matrix = WordSearchMatrix::new();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let words = read_word_list(&args);
+    // Every occurrence is listed when the words were given explicitly
+    let print_matches = args.iter().any(|a| a == "--words" || a == "--word");
+    let (templates, variants) = read_templates(&args);
+    let print_template_matches = args.iter().any(|a| a == "--template");
+    // --render ansi|plain prints the grid with the found words highlighted
+    let render_mode = args
+        .iter()
+        .position(|a| a == "--render")
+        .map(|i| RenderMode::parse(args.get(i + 1).expect("Missing render mode")));
+
+    // --stream prints every occurrence as soon as it is found, in no particular order and without
+    // keeping them in memory
+    let stream = args.iter().any(|a| a == "--stream");
+    // --wrap continues lines and templates on the opposite edge of the grid
+    let wrap = args.iter().any(|a| a == "--wrap");
+    // Each cell is a grapheme, compared after the normalization asked for
+    let normalization = Normalization::from_args(&args);
+    if let Some(i) = args.iter().position(|a| a == "--generate") {
+        generate(&args, &words, args.get(i + 1).expect("Missing puzzle size"), wrap, normalization);
+        return;
+    }
+
+    let mut matrix = WordSearchMatrix::new(wrap, normalization);
     let stdin = std::io::stdin();
     {
         let lock = stdin.lock();
@@ -208,19 +647,55 @@ fn main() {
             matrix.add_row(line.unwrap());
         }
     }
+    matrix.finish();
     // dprintln!("input: {:?}", matrix);
 
-    let search_lines = matrix.get_search_lines();
-    dprintln!("search_lines: {:?}", search_lines.iter().count());
-    let result = search_lines
-        .iter()
-        .map(|line| line.find_xmas_ident())
-        .flatten()
-        .collect::<HashSet<_>>();
-    println!("Result: {}", result.len());
+    dprintln!("search_lines: {:?}", matrix.get_search_lines(0).len());
+    let automaton = matrix.automaton(&words);
+    let total = if stream {
+        assert!(render_mode.is_none(), "--stream can not be combined with --render");
+        let mut total = 0;
+        for (word, ident) in matrix.matches(&automaton) {
+            println!("{} ({}, {}) {}", words[word], ident.x, ident.y, ident.direction_name());
+            total += 1;
+        }
+        total
+    } else if print_matches || render_mode.is_some() {
+        let found = matrix.find_words(&automaton, words.len());
+        let total = found.iter().map(|f| f.len()).sum::<usize>();
+        if let Some(mode) = render_mode {
+            let coverage = matrix.coverage(&found, &automaton);
+            print!("{}", render::render(&matrix.cells, matrix.width, &matrix.symbols, &coverage, mode));
+        }
+        if print_matches {
+            print!("{}", format_found(&words, found));
+        }
+        total
+    } else {
+        matrix.count_matches(&automaton)
+    };
+    println!("Result: {}", total);
 
-    let sub_grids = matrix.get_sub_grids();
-    let result = sub_grids.iter().filter(|grid| grid.is_x()).count();
+    let variants = variants
+        .iter()
+        .map(|v| {
+            v.intern(|g| {
+                let id = matrix.symbols.intern(g);
+                matrix.symbols.key(id)
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut template_matches = matrix.find_templates(&variants);
+    if print_template_matches {
+        template_matches.sort();
+        for (i, template) in templates.iter().enumerate() {
+            let found = template_matches.iter().filter(|m| m.template == i).collect::<Vec<_>>();
+            println!("{}: {}", template.name, found.len());
+            for m in found {
+                println!("  ({}, {}) {}", m.x, m.y, m.orientation);
+            }
+        }
+    }
 
-    println!("Result: {}", result);
+    println!("Result: {}", template_matches.len());
 }
@@ -0,0 +1,50 @@
+use crate::symbols::{Symbols, HOLE};
+
+const HIGHLIGHT: &str = "\x1b[1;32m";
+const OVERLAP: &str = "\x1b[1;33;4m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+// Combining low line, underlines any grapheme it follows without taking a column of its own
+const PLAIN_OVERLAP: char = '\u{332}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+    // Found letters in colour, letters in several matches underlined, the rest dimmed
+    Ansi,
+    // Letters outside of any match replaced by '.', letters in several matches underlined
+    Plain,
+}
+
+impl RenderMode {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "ansi" => RenderMode::Ansi,
+            "plain" => RenderMode::Plain,
+            _ => panic!("Unknown render mode {}, expected ansi or plain", s),
+        }
+    }
+}
+
+// Grid rows with each letter styled by the number of matches covering it, holes padding short
+// rows are left out
+pub fn render(cells: &[u16], width: usize, symbols: &Symbols, coverage: &[u32], mode: RenderMode) -> String {
+    let mut result = String::new();
+    for (row, counts) in cells.chunks(width.max(1)).zip(coverage.chunks(width.max(1))) {
+        for (&letter, &count) in row.iter().zip(counts).filter(|(&letter, _)| letter != HOLE) {
+            let letter = symbols.text(letter);
+            match (mode, count) {
+                (RenderMode::Ansi, 0) => result.push_str(&format!("{}{}{}", DIM, letter, RESET)),
+                (RenderMode::Ansi, 1) => result.push_str(&format!("{}{}{}", HIGHLIGHT, letter, RESET)),
+                (RenderMode::Ansi, _) => result.push_str(&format!("{}{}{}", OVERLAP, letter, RESET)),
+                (RenderMode::Plain, 0) => result.push('.'),
+                (RenderMode::Plain, 1) => result.push_str(letter),
+                (RenderMode::Plain, _) => {
+                    result.push_str(letter);
+                    result.push(PLAIN_OVERLAP);
+                }
+            }
+        }
+        result.push('\n');
+    }
+    result
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
+
+// Fills the end of rows shorter than the widest one, no grapheme is given this id or key
+pub const HOLE: u16 = 0;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Normalization {
+    pub fold_case: bool,
+    pub strip_diacritics: bool,
+}
+
+impl Normalization {
+    // --fold-case and --strip-diacritics. Folding is full Unicode case folding rather than
+    // lowercasing, so that final sigma matches sigma and capital sharp s matches ß.
+    pub fn from_args(args: &[String]) -> Self {
+        Normalization {
+            fold_case: args.iter().any(|a| a == "--fold-case"),
+            strip_diacritics: args.iter().any(|a| a == "--strip-diacritics"),
+        }
+    }
+
+    // Text graphemes are compared by, always in one normal form so that precomposed letters and
+    // letters followed by combining marks are the same
+    fn key(&self, grapheme: &str) -> String {
+        let text = match self.fold_case {
+            true => caseless::default_case_fold_str(grapheme),
+            false => grapheme.to_string(),
+        };
+        match self.strip_diacritics {
+            true => text.nfd().filter(|&c| !is_combining_mark(c)).collect(),
+            false => text.nfc().collect(),
+        }
+    }
+}
+
+// Graphemes that become cells of the grid, the rest of a row only separates them
+fn is_letter(grapheme: &str) -> bool {
+    grapheme.chars().next().is_some_and(char::is_alphabetic)
+}
+
+// Interned graphemes. Cells hold the id of their grapheme as written, so that it can be shown
+// again, and are matched by the id of its key.
+#[derive(Debug, Clone)]
+pub struct Symbols {
+    normalization: Normalization,
+    ids: HashMap<String, u16>,
+    texts: Vec<String>,
+    keys: Vec<u16>,
+    key_ids: HashMap<String, u16>,
+    // Ids of the ASCII letters, so that ASCII rows skip segmentation and hashing
+    ascii: [u16; 128],
+}
+
+impl Symbols {
+    pub fn new(normalization: Normalization) -> Self {
+        Symbols {
+            normalization,
+            ids: HashMap::new(),
+            texts: vec![String::new()],
+            keys: vec![HOLE],
+            key_ids: HashMap::new(),
+            ascii: [HOLE; 128],
+        }
+    }
+
+    pub fn intern(&mut self, grapheme: &str) -> u16 {
+        if let Some(&id) = self.ids.get(grapheme) {
+            return id;
+        }
+        let id = u16::try_from(self.texts.len()).expect("Too many distinct graphemes");
+        let next = self.key_ids.len() as u16 + 1;
+        let key = *self.key_ids.entry(self.normalization.key(grapheme)).or_insert(next);
+        self.ids.insert(grapheme.to_string(), id);
+        self.texts.push(grapheme.to_string());
+        self.keys.push(key);
+        id
+    }
+
+    fn intern_ascii(&mut self, byte: u8) -> u16 {
+        if self.ascii[byte as usize] == HOLE {
+            self.ascii[byte as usize] = self.intern(&(byte as char).to_string());
+        }
+        self.ascii[byte as usize]
+    }
+
+    // Appends the letters of a row of the grid as cells
+    pub fn intern_row(&mut self, row: &str, cells: &mut Vec<u16>) {
+        if row.is_ascii() {
+            for byte in row.bytes().filter(u8::is_ascii_alphabetic) {
+                cells.push(self.intern_ascii(byte));
+            }
+        } else {
+            for grapheme in row.graphemes(true).filter(|g| is_letter(g)) {
+                cells.push(self.intern(grapheme));
+            }
+        }
+    }
+
+    // Every grapheme of a word or template row, letter or not
+    pub fn intern_all(&mut self, text: &str) -> Vec<u16> {
+        text.graphemes(true).map(|g| self.intern(g)).collect()
+    }
+
+    pub fn text(&self, id: u16) -> &str {
+        &self.texts[id as usize]
+    }
+
+    pub fn key(&self, id: u16) -> u16 {
+        self.keys[id as usize]
+    }
+
+    pub fn is_letter(&self, id: u16) -> bool {
+        is_letter(self.text(id))
+    }
+}
+
+// Graphemes of a line of text, for anything that is parsed cell by cell
+pub fn graphemes(text: &str) -> impl Iterator<Item = &str> {
+    text.graphemes(true)
+}
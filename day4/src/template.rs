@@ -0,0 +1,170 @@
+use crate::symbols::graphemes;
+
+// Orientation of a template variant, applied as reflection first, then clockwise rotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Orientation {
+    pub rotation: u16,
+    pub reflected: bool,
+}
+
+impl std::fmt::Display for Orientation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rotated {}", self.rotation)?;
+        if self.reflected {
+            write!(f, ", reflected")?;
+        }
+        Ok(())
+    }
+}
+
+// Rows of cells, None is a wildcard that matches any letter
+type Cells<T> = Vec<Vec<Option<T>>>;
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    // Graphemes as written in the template
+    cells: Cells<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variant<T> {
+    pub template: usize,
+    pub orientation: Orientation,
+    cells: Cells<T>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TemplateMatch {
+    pub template: usize,
+    // Top left corner of the oriented template
+    pub y: usize,
+    pub x: usize,
+    pub orientation: Orientation,
+}
+
+impl Template {
+    // Rows of the template as text, shorter rows are padded with wildcards
+    pub fn parse(name: &str, text: &str, wildcard: char) -> Self {
+        let mut cells = text
+            .lines()
+            .map(|row| {
+                graphemes(row)
+                    .map(|g| match g.chars().eq([wildcard]) {
+                        true => None,
+                        false => Some(g.to_string()),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let width = cells.iter().map(|row| row.len()).max().unwrap_or(0);
+        for row in cells.iter_mut() {
+            row.resize(width, None);
+        }
+        Template {
+            name: name.to_string(),
+            cells,
+        }
+    }
+
+    // Templates in a file, separated by blank lines
+    pub fn load(path: &str, wildcard: char) -> Vec<Self> {
+        let text = std::fs::read_to_string(path)
+            .unwrap_or_else(|e| panic!("Failed to read templates {}: {}", path, e));
+        text.split("\n\n")
+            .filter(|block| !block.trim().is_empty())
+            .enumerate()
+            .map(|(i, block)| Self::parse(&format!("{}#{}", path, i + 1), block.trim_matches('\n'), wildcard))
+            .collect()
+    }
+
+    // Two MAS crossing on their A, in any of the four rotations
+    pub fn x_mas() -> Self {
+        Self::parse("X-MAS", "M.S\n.A.\nM.S", '.')
+    }
+
+    fn rotate(cells: &Cells<String>) -> Cells<String> {
+        let height = cells.len();
+        let width = cells.first().map_or(0, |row| row.len());
+        (0..width)
+            .map(|x| (0..height).rev().map(|y| cells[y][x].clone()).collect())
+            .collect()
+    }
+
+    fn reflect(cells: &Cells<String>) -> Cells<String> {
+        cells
+            .iter()
+            .map(|row| row.iter().rev().cloned().collect())
+            .collect()
+    }
+
+    // Distinct orientations of the template, symmetric templates yield fewer variants
+    pub fn variants(&self, template: usize, rotations: bool, reflections: bool) -> Vec<Variant<String>> {
+        let mut variants: Vec<Variant<String>> = vec![];
+        for reflected in [false, true] {
+            if reflected && !reflections {
+                continue;
+            }
+            let mut cells = match reflected {
+                true => Self::reflect(&self.cells),
+                false => self.cells.clone(),
+            };
+            for rotation in [0, 90, 180, 270] {
+                if rotation > 0 {
+                    if !rotations {
+                        break;
+                    }
+                    cells = Self::rotate(&cells);
+                }
+                if variants.iter().all(|v| v.cells != cells) {
+                    variants.push(Variant {
+                        template,
+                        orientation: Orientation {
+                            rotation,
+                            reflected,
+                        },
+                        cells: cells.clone(),
+                    });
+                }
+            }
+        }
+        variants
+    }
+}
+
+impl Variant<String> {
+    // Variant matched by symbol instead of text
+    pub fn intern(&self, mut intern: impl FnMut(&str) -> u16) -> Variant<u16> {
+        Variant {
+            template: self.template,
+            orientation: self.orientation,
+            cells: self
+                .cells
+                .iter()
+                .map(|row| row.iter().map(|cell| cell.as_deref().map(&mut intern)).collect())
+                .collect(),
+        }
+    }
+}
+
+impl<T: PartialEq> Variant<T> {
+    pub fn height(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn width(&self) -> usize {
+        self.cells.first().map_or(0, |row| row.len())
+    }
+
+    // Whether the variant matches with its top left corner at (x, y), `cell` returns None outside
+    // of the grid
+    pub fn matches_at(&self, x: usize, y: usize, cell: impl Fn(usize, usize) -> Option<T>) -> bool {
+        self.cells.iter().enumerate().all(|(dy, row)| {
+            row.iter().enumerate().all(|(dx, expected)| match (expected, cell(x + dx, y + dy)) {
+                (_, None) => false,
+                (None, Some(_)) => true,
+                (Some(expected), Some(actual)) => *expected == actual,
+            })
+        })
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::{PageList, PageRules, Rule};
+
+// Findings about a rule set. Implications chain rules through other pages, so they only hold for
+// updates that contain the pages in between.
+#[derive(Debug)]
+pub struct Analysis {
+    // X|Y together with Y|X, and pages that must come before themselves
+    contradictions: Vec<(Rule, Rule)>,
+    // Rules listed more than once, with their count
+    duplicates: Vec<(Rule, usize)>,
+    // Rules that follow from the others, with the shortest chain of pages that implies them
+    implied: Vec<(Rule, Vec<i32>)>,
+    // Rules left after dropping, one at a time, every rule implied by the ones still kept. For
+    // rules without cycles this is the unique transitive reduction.
+    reduction: Vec<Rule>,
+    // Pages that appear in updates but in no rule
+    unconstrained_pages: Vec<i32>,
+}
+
+// Shortest chain of rules from X to Y that does not use X|Y itself or any of the dropped rules
+fn chain_around(successors: &HashMap<i32, Vec<i32>>, rule: Rule, dropped: &HashSet<(i32, i32)>) -> Option<Vec<i32>> {
+    let mut previous = HashMap::from([(rule.x, rule.x)]);
+    let mut queue = VecDeque::from([rule.x]);
+    while let Some(page) = queue.pop_front() {
+        for &next in successors.get(&page).into_iter().flatten() {
+            if (page, next) == (rule.x, rule.y) || dropped.contains(&(page, next)) || previous.contains_key(&next) {
+                continue;
+            }
+            previous.insert(next, page);
+            if next == rule.y {
+                let mut chain = vec![next];
+                while *chain.last().unwrap() != rule.x {
+                    chain.push(previous[chain.last().unwrap()]);
+                }
+                chain.reverse();
+                return Some(chain);
+            }
+            queue.push_back(next);
+        }
+    }
+    None
+}
+
+impl Analysis {
+    pub fn new(rules: &PageRules, book_list: &[PageList]) -> Self {
+        // Distinct rules in the order they were first listed
+        let mut counts = HashMap::new();
+        let mut distinct = vec![];
+        for rule in rules.rules.iter() {
+            let count = counts.entry((rule.x, rule.y)).or_insert(0);
+            if *count == 0 {
+                distinct.push(*rule);
+            }
+            *count += 1;
+        }
+        let mut successors = HashMap::new();
+        for rule in distinct.iter() {
+            successors.entry(rule.x).or_insert(Vec::new()).push(rule.y);
+        }
+
+        let contradictions = distinct
+            .iter()
+            .filter(|rule| rule.x < rule.y && counts.contains_key(&(rule.y, rule.x)) || rule.x == rule.y)
+            .map(|&rule| (rule, Rule { x: rule.y, y: rule.x }))
+            .collect();
+        let duplicates = distinct
+            .iter()
+            .filter(|rule| counts[&(rule.x, rule.y)] > 1)
+            .map(|&rule| (rule, counts[&(rule.x, rule.y)]))
+            .collect();
+        let implied = distinct
+            .iter()
+            .filter_map(|&rule| Some((rule, chain_around(&successors, rule, &HashSet::new())?)))
+            .collect();
+
+        let mut dropped = HashSet::new();
+        for rule in distinct.iter() {
+            if chain_around(&successors, *rule, &dropped).is_some() {
+                dropped.insert((rule.x, rule.y));
+            }
+        }
+        let reduction = distinct
+            .iter()
+            .filter(|rule| !dropped.contains(&(rule.x, rule.y)))
+            .copied()
+            .collect();
+
+        let ruled_pages = distinct.iter().flat_map(|rule| [rule.x, rule.y]).collect::<HashSet<_>>();
+        let unconstrained_pages = book_list
+            .iter()
+            .flat_map(|page_list| page_list.pages.iter().copied())
+            .filter(|page| !ruled_pages.contains(page))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        Analysis {
+            contradictions,
+            duplicates,
+            implied,
+            reduction,
+            unconstrained_pages,
+        }
+    }
+}
+
+impl std::fmt::Display for Analysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Contradictions: {}", self.contradictions.len())?;
+        for (rule, opposite) in self.contradictions.iter() {
+            match rule.x == rule.y {
+                true => writeln!(f, "  {} puts a page before itself", rule)?,
+                false => writeln!(f, "  {} and {}", rule, opposite)?,
+            }
+        }
+        writeln!(f, "Duplicates: {}", self.duplicates.len())?;
+        for (rule, count) in self.duplicates.iter() {
+            writeln!(f, "  {} listed {} times", rule, count)?;
+        }
+        writeln!(f, "Implied: {}", self.implied.len())?;
+        for (rule, chain) in self.implied.iter() {
+            let chain = chain.iter().map(|page| page.to_string()).collect::<Vec<_>>();
+            writeln!(f, "  {} through {}", rule, chain.join(" -> "))?;
+        }
+        writeln!(f, "Transitive reduction: {}", self.reduction.len())?;
+        for rule in self.reduction.iter() {
+            writeln!(f, "  {}", rule)?;
+        }
+        writeln!(f, "Pages without rules: {}", self.unconstrained_pages.len())?;
+        for page in self.unconstrained_pages.iter() {
+            writeln!(f, "  {}", page)?;
+        }
+        Ok(())
+    }
+}
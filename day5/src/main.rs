@@ -1,9 +1,12 @@
 use std::{
-    clone,
-    collections::{HashMap, HashSet, VecDeque},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
     io::BufRead,
 };
 
+mod analysis;
+use analysis::Analysis;
+
 macro_rules! dprintln {
     ($($arg:tt)*) => {
         #[cfg(feature = "debug")]
@@ -20,6 +23,12 @@ struct Rule {
     y: i32,
 }
 
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}|{}", self.x, self.y)
+    }
+}
+
 #[derive(Debug)]
 struct PageRules {
     rules: Vec<Rule>,
@@ -51,12 +60,50 @@ impl OptimizedPageRules {
             y_to_x
                 .entry(rule.y)
                 .or_insert(Vec::new())
-                .push(rule.clone());
+                .push(*rule);
         }
         OptimizedPageRules { rules, y_to_x }
     }
 }
 
+// A rule broken by an update, page Y at `y_index` comes before page X at `x_index`
+#[derive(Debug, Clone, Copy)]
+struct Violation {
+    rule: Rule,
+    x_index: usize,
+    y_index: usize,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.x_index == self.y_index {
+            return write!(f, "{} must come before itself, at index {}", self.rule.x, self.x_index);
+        }
+        write!(
+            f,
+            "{} must come before {}, but {} is at index {} and {} at index {}",
+            self.rule.x, self.rule.y, self.rule.y, self.y_index, self.rule.x, self.x_index
+        )
+    }
+}
+
+// Pages whose rules contradict each other, each page must come before the next and the last one
+// before the first
+#[derive(Debug)]
+struct CycleError {
+    pages: Vec<i32>,
+}
+
+impl std::fmt::Display for CycleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "rules form a cycle: ")?;
+        for page in self.pages.iter() {
+            write!(f, "{} -> ", page)?;
+        }
+        write!(f, "{}", self.pages[0])
+    }
+}
+
 #[derive(Debug, Clone)]
 struct PageList {
     pages: Vec<i32>,
@@ -71,62 +118,107 @@ impl PageList {
         PageList { pages }
     }
 
-    fn is_valid_page(
-        pages: &Vec<i32>,
-        page: i32,
-        optimized_rules: &OptimizedPageRules,
-        visited: &HashSet<i32>,
-    ) -> bool {
-        for rule in optimized_rules.y_to_x.get(&page).unwrap_or(&vec![]).iter() {
-            if !pages.contains(&rule.x) {
-                continue;
-            }
-            if !visited.contains(&rule.x) {
-                return false;
+    fn is_valid(&self, optimized_rules: &OptimizedPageRules) -> bool {
+        self.violations(optimized_rules).is_empty()
+    }
+
+    // Every rule the update breaks, in the order of the later page of each pair. A rule putting a
+    // page before itself is broken by any update containing that page.
+    fn violations(&self, optimized_rules: &OptimizedPageRules) -> Vec<Violation> {
+        let mut violations = vec![];
+        for (x_index, x) in self.pages.iter().enumerate() {
+            for (y_index, y) in self.pages[..=x_index].iter().enumerate() {
+                let rules = optimized_rules.y_to_x.get(y).into_iter().flatten();
+                for rule in rules.filter(|rule| rule.x == *x) {
+                    violations.push(Violation {
+                        rule: *rule,
+                        x_index,
+                        y_index,
+                    });
+                }
             }
         }
-        true
+        violations
     }
 
-    fn is_valid(&self, optimized_rules: &OptimizedPageRules) -> bool {
-        let mut visited = HashSet::new();
-        for page in &self.pages {
-            if !Self::is_valid_page(&self.pages, *page, optimized_rules, &visited) {
-                return false;
+    // Topological sort (Kahn's algorithm) over the rules between pages of this update, pages that
+    // are free to go anywhere keep their original order
+    fn correctly_ordered(&self, optimized_rules: &OptimizedPageRules) -> Result<Self, CycleError> {
+        // Graph over positions in the update, so repeated pages are placed like any other
+        let mut positions = HashMap::new();
+        for (i, page) in self.pages.iter().enumerate() {
+            positions.entry(*page).or_insert(Vec::new()).push(i);
+        }
+        let mut successors = vec![vec![]; self.pages.len()];
+        let mut in_degrees = vec![0; self.pages.len()];
+        for rule in optimized_rules.rules.rules.iter() {
+            let (Some(xs), Some(ys)) = (positions.get(&rule.x), positions.get(&rule.y)) else {
+                continue;
+            };
+            for &x in xs {
+                for &y in ys {
+                    successors[x].push(y);
+                    in_degrees[y] += 1;
+                }
             }
-            visited.insert(*page);
         }
-        true
-    }
 
-    fn correctly_ordered(&self, optimized_rules: &OptimizedPageRules) -> Self {
-        let mut queue = VecDeque::new();
+        let mut ready = (0..self.pages.len())
+            .filter(|&i| in_degrees[i] == 0)
+            .map(Reverse)
+            .collect::<BinaryHeap<_>>();
         let mut final_order = vec![];
-        let mut visited = HashSet::new();
-        for page in &self.pages {
-            if !Self::is_valid_page(&self.pages, *page, optimized_rules, &visited) {
-                queue.push_back(*page);
-            } else {
-                final_order.push(*page);
-                visited.insert(*page);
+        while let Some(Reverse(i)) = ready.pop() {
+            final_order.push(self.pages[i]);
+            for &next in successors[i].iter() {
+                in_degrees[next] -= 1;
+                if in_degrees[next] == 0 {
+                    ready.push(Reverse(next));
+                }
+            }
+        }
+        if final_order.len() < self.pages.len() {
+            return Err(self.find_cycle(&successors, &in_degrees));
+        }
+        Ok(PageList { pages: final_order })
+    }
+
+    // Every page left over by the sort still waits for a page that was left over too, so walking
+    // back through those from any of them has to run into a cycle
+    fn find_cycle(&self, successors: &[Vec<usize>], in_degrees: &[usize]) -> CycleError {
+        let mut predecessors = vec![vec![]; successors.len()];
+        for (i, next) in successors.iter().enumerate() {
+            for &next in next {
+                predecessors[next].push(i);
             }
         }
-        while !queue.is_empty() {
-            let page = queue.pop_front().unwrap();
-            if Self::is_valid_page(&self.pages, page, optimized_rules, &visited) {
-                final_order.push(page);
-                visited.insert(page);
-            } else {
-                queue.push_back(page);
+        let mut path = vec![(0..in_degrees.len()).find(|&i| in_degrees[i] > 0).unwrap()];
+        let mut seen = HashMap::from([(path[0], 0)]);
+        loop {
+            let current = *path.last().unwrap();
+            let previous = *predecessors[current]
+                .iter()
+                .find(|&&p| in_degrees[p] > 0)
+                .unwrap();
+            if let Some(&start) = seen.get(&previous) {
+                // The path was walked backwards, the rules go the other way
+                let pages = path[start..].iter().rev().map(|&i| self.pages[i]).collect();
+                return CycleError { pages };
             }
+            seen.insert(previous, path.len());
+            path.push(previous);
         }
-        assert!(final_order.len() == self.pages.len());
-        PageList { pages: final_order }
     }
 }
 
 fn main() {
     let start = std::time::Instant::now();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    // --analyze reports contradictory, duplicate and implied rules, the transitive reduction of
+    // the rules and the pages no rule mentions
+    let analyze = args.iter().any(|a| a == "--analyze");
+    // --explain lists the rules each invalid update breaks
+    let explain = args.iter().any(|a| a == "--explain");
     // let mut matrix = WordSearchMatrix::new();
     let mut page_rules = PageRules::new();
     let mut book_list = vec![];
@@ -151,18 +243,45 @@ fn main() {
     dprintln!("Rules: {:?}", page_rules);
     dprintln!("Book List: {:?}", book_list);
 
+    if analyze {
+        print!("{}", Analysis::new(&page_rules, &book_list));
+    }
+
     let optimized_rules = OptimizedPageRules::new(page_rules);
 
+    if explain {
+        for page_list in book_list.iter() {
+            let violations = page_list.violations(&optimized_rules);
+            if violations.is_empty() {
+                continue;
+            }
+            let pages = page_list.pages.iter().map(|page| page.to_string()).collect::<Vec<_>>();
+            println!("Update {} is invalid:", pages.join(","));
+            for violation in violations {
+                println!("  {}", violation);
+            }
+        }
+    }
+
     let res1 = book_list
         .iter()
         .filter(|page_list| page_list.is_valid(&optimized_rules))
         .map(|page_list| page_list.pages[page_list.pages.len() / 2])
         .sum::<i32>();
 
+    // Updates whose rules form a cycle are reported and left out of part 2
+    let mut unordered = 0;
     let res2 = book_list
         .iter()
         .filter(|page_list| !page_list.is_valid(&optimized_rules))
-        .map(|page_list| page_list.correctly_ordered(&optimized_rules))
+        .filter_map(|page_list| match page_list.correctly_ordered(&optimized_rules) {
+            Ok(ordered) => Some(ordered),
+            Err(e) => {
+                eprintln!("Cannot order update {:?}: {}", page_list.pages, e);
+                unordered += 1;
+                None
+            }
+        })
         .collect::<Vec<_>>();
 
     let res2 = res2
@@ -175,4 +294,9 @@ fn main() {
     println!("Result: {}", res2);
 
     dprintln!("Time: {:?}", start.elapsed());
+
+    if unordered > 0 {
+        eprintln!("{} updates could not be ordered", unordered);
+        std::process::exit(1);
+    }
 }